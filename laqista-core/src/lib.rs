@@ -8,18 +8,83 @@ pub mod session;
 pub mod tensor;
 pub mod wasm;
 
+use std::collections::HashMap;
+
 use uuid::Uuid;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct DeploymentInfo {
     pub id: Uuid,
     pub name: String,
     pub source: String,
+    /// Resources that each instance requires
+    pub resources: Resources,
+    pub constraints: Constraints,
+}
+
+/// Resources are in the units of the reported utilization:
+/// CPU and GPU in percent of a server, RAM and VRAM in megabytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Resources {
+    pub cpu: u32,
+    pub ram: u32,
+    pub gpu: u32,
+    pub vram: u32,
+}
+
+/// Constraints on where and how many instances of a deployment run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Constraints {
+    /// Instances can only run on GPUs. Otherwise they can also run on CPUs.
+    pub gpu_required: bool,
+    pub min_replicas: u32,
+    /// `None` for unlimited
+    pub max_replicas: Option<u32>,
+    /// Labels that a server must have to run an instance
+    pub affinity: HashMap<String, String>,
+    /// Labels that a server must not have to run an instance
+    pub anti_affinity: HashMap<String, String>,
 }
 
 impl DeploymentInfo {
     pub fn new(name: String, source: String) -> Self {
         let id = Uuid::new_v4();
-        Self { name, source, id }
+        let resources = Resources::default();
+        let constraints = Constraints::default();
+        Self {
+            name,
+            source,
+            id,
+            resources,
+            constraints,
+        }
+    }
+
+    pub fn with_resources(mut self, resources: Resources) -> Self {
+        self.resources = resources;
+        self
+    }
+
+    pub fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+}
+
+impl Resources {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+impl Constraints {
+    /// replicas_allowed returns whether `count` instances stay within `max_replicas`.
+    pub fn replicas_allowed(&self, count: usize) -> bool {
+        self.max_replicas.map_or(true, |max| count <= max as usize)
+    }
+
+    /// initial_replicas is the number of instances to spawn on deployment.
+    pub fn initial_replicas(&self) -> usize {
+        self.min_replicas.max(1) as _
     }
 }
@@ -6,15 +6,18 @@ use hex::FromHex;
 use laqista_core::DeploymentInfo;
 use sha2::{Digest, Sha256};
 use tokio::sync::Mutex;
+use tracing::trace;
 use uuid::Uuid;
 
 use crate::{
+    proto::DeploymentVersion,
     server::{StateCommand, StateSender},
     utils::IdMap,
+    Error as LaqistaError,
 };
 
 use super::{
-    fs::{read_apps, read_binary, write_info, write_tgz},
+    fs::{read_apps, read_binary, remove_dir, write_info, write_tgz},
     http::download,
 };
 
@@ -65,6 +68,10 @@ impl DeploymentDatabase {
         Self::read_dir(root, tx).unwrap()
     }
 
+    pub fn root(&self) -> &PathBuf {
+        &self.root
+    }
+
     pub async fn add_instance(
         &mut self,
         deployment: &DeploymentInfo,
@@ -80,6 +87,27 @@ impl DeploymentDatabase {
         Ok(())
     }
 
+    pub async fn has_instance(&self, id: &Uuid) -> bool {
+        self.inner.lock().await.instances.contains(id)
+    }
+
+    pub async fn remove_instance(&mut self, id: &Uuid) -> Result<(), Box<dyn Error>> {
+        {
+            let mut inner = self.inner.lock().await;
+            let index = inner
+                .instances
+                .iter()
+                .position(|i| i == id)
+                .ok_or(format!("Instance not found: {id}"))?;
+            inner.instances.remove(index);
+        }
+
+        // Same as `add_instance`, the daemon restarts to stop serving the application.
+        self.state_tx.send(StateCommand::Keep).await?;
+
+        Ok(())
+    }
+
     pub async fn add_app(&self, info: &DeploymentInfo) -> Result<(), Box<dyn Error>> {
         let bin = download(info.source.clone()).await?;
 
@@ -90,6 +118,22 @@ impl DeploymentDatabase {
         Ok(())
     }
 
+    /// `remove_app` deletes the stored binaries of the application.
+    /// A running instance keeps being served until it is removed by `remove_instance`.
+    pub async fn remove_app(&self, id: &Uuid) -> Result<(), Box<dyn Error>> {
+        let mut inner = self.inner.lock().await;
+
+        let app = inner
+            .apps
+            .0
+            .remove(id)
+            .ok_or(format!("Application not found: {id}"))?;
+
+        remove_dir(&app_dir(&self.root, &app.info))?;
+
+        Ok(())
+    }
+
     pub async fn get(
         &self,
         info: &DeploymentInfo,
@@ -115,6 +159,18 @@ impl DeploymentDatabase {
         Ok(bytes)
     }
 
+    /// `versions` returns every saved deployment of the application, from the oldest.
+    pub async fn versions(&self, id: &Uuid) -> Vec<SavedDeployment> {
+        self.inner
+            .lock()
+            .await
+            .apps
+            .0
+            .get(id)
+            .map(|a| a.deployments.clone())
+            .unwrap_or_default()
+    }
+
     pub async fn lookup(&self, name: &str) -> Option<DeploymentInfo> {
         self.inner
             .lock()
@@ -126,6 +182,19 @@ impl DeploymentDatabase {
             .map(|(_, a)| a.info.clone())
     }
 
+    /// `lookup_instance` works the same as `lookup`, except for this only returns
+    /// applications that have an running instance on this server.
+    pub async fn lookup_instance(&self, name: &str) -> Option<DeploymentInfo> {
+        let inner = self.inner.lock().await;
+
+        inner
+            .apps
+            .0
+            .iter()
+            .find(|(id, a)| &a.info.name == name && inner.instances.contains(id))
+            .map(|(_, a)| a.info.clone())
+    }
+
     async fn save(
         &self,
         info: &DeploymentInfo,
@@ -197,6 +266,35 @@ impl SavedDeployment {
     }
 }
 
+impl Into<DeploymentVersion> for SavedDeployment {
+    fn into(self) -> DeploymentVersion {
+        let timestamp = Some(prost_types::Timestamp {
+            seconds: self.timestamp.timestamp(),
+            nanos: self.timestamp.timestamp_subsec_nanos() as _,
+        });
+        let hash = self.hash.to_vec();
+
+        DeploymentVersion { timestamp, hash }
+    }
+}
+
+impl TryFrom<DeploymentVersion> for SavedDeployment {
+    type Error = LaqistaError;
+    fn try_from(version: DeploymentVersion) -> Result<Self, Self::Error> {
+        let ts = version
+            .timestamp
+            .ok_or("Timestamp cannot be empty".to_owned())?;
+        let timestamp = Local
+            .timestamp_opt(ts.seconds, ts.nanos as _)
+            .single()
+            .ok_or("Invalid timestamp".to_owned())?;
+
+        let hash = Hash::try_from(version.hash).map_err(|_| "Invalid hash length".to_owned())?;
+
+        Ok(Self { timestamp, hash })
+    }
+}
+
 fn app_root_dir(root: &PathBuf) -> PathBuf {
     root.join("apps")
 }
@@ -215,7 +313,7 @@ impl Target {
     pub fn extension_matches(&self, filename: &OsStr) -> bool {
         let extension = self.to_string();
         let matches = filename.to_str().unwrap().ends_with(&extension);
-        dbg!(self, filename, extension, matches);
+        trace!(%extension, ?filename, matches, "matched the extension");
         matches
     }
 }
@@ -238,13 +336,16 @@ mod test {
     #[tokio::test]
     async fn db_test() {
         let (tx, _) = mpsc::channel(1);
-        let db = DeploymentDatabase::read_dir(PathBuf::from("./.laqista-test"), tx).unwrap();
+        let root = tempfile::tempdir().unwrap();
+        let db = DeploymentDatabase::read_dir(root.path().to_owned(), tx).unwrap();
 
         let info = DeploymentInfo {
             id: Uuid::new_v4(),
             name: "test".to_owned(),
             source: "https://github.com/kino-ma/laqista/releases/download/v0.1.0/face_v0.1.0.tgz"
                 .to_owned(),
+            resources: Default::default(),
+            constraints: Default::default(),
         };
         db.add_app(&info).await.unwrap();
     }
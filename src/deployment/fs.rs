@@ -9,6 +9,7 @@ use bytes::Bytes;
 use flate2::read::GzDecoder;
 use laqista_core::DeploymentInfo;
 use tar::Archive;
+use tracing::debug;
 
 use crate::{proto::Deployment, utils::IdMap};
 
@@ -95,7 +96,7 @@ pub fn write_tgz(path: &PathBuf, tgz: Bytes) -> IOResult<()> {
         })
         .collect::<IOResult<Vec<_>>>()?;
 
-    println!("write_tgz: Written files: {:?}", written_files);
+    debug!(?written_files, "extracted the archive");
 
     Ok(())
 }
@@ -111,6 +112,15 @@ pub fn write_info(path: &PathBuf, info: &DeploymentInfo) -> IOResult<()> {
     Ok(())
 }
 
+pub fn remove_dir(path: &PathBuf) -> IOResult<()> {
+    use std::io::ErrorKind::*;
+
+    std::fs::remove_dir_all(path).or_else(|e| match e.kind() {
+        NotFound => Ok(()),
+        _ => Err(e),
+    })
+}
+
 pub fn read_binary(dir: &PathBuf, target: Target) -> IOResult<Bytes> {
     let mut dir = std::fs::read_dir(dir)?;
 
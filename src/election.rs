@@ -0,0 +1,193 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use futures::future;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::proto::server_daemon_client::ServerDaemonClient;
+use crate::proto::{Group, VoteRequest, VoteResponse};
+use crate::scheduler::Cluster;
+use crate::telemetry;
+use crate::utils::random_below;
+use crate::{GroupInfo, Result, ServerInfo};
+
+const ELECTION_TIMEOUT: Duration = Duration::from_millis(1500);
+const ELECTION_TIMEOUT_JITTER_MILLIS: u64 = 1500;
+const VOTE_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Election holds the state for electing a scheduler, which lives across restarts of the daemon.
+/// Terms of the election are the same as the group numbers.
+#[derive(Clone, Debug)]
+pub struct Election {
+    state: Arc<Mutex<ElectionState>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ElectionState {
+    /// The latest term this server has seen
+    pub number: u32,
+    /// The candidate this server has voted for in the current term
+    pub voted_for: Option<Uuid>,
+    /// This server does not start an election until the deadline
+    pub deadline: Instant,
+}
+
+impl Election {
+    pub fn new() -> Self {
+        let state = ElectionState {
+            number: 0,
+            voted_for: None,
+            deadline: Instant::now(),
+        };
+
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    pub async fn number(&self) -> u32 {
+        self.state.lock().await.number
+    }
+
+    /// observe updates the latest term with `number`.
+    /// Returns `false` if `number` is older than the latest term, i.e., the sender is stale.
+    pub async fn observe(&self, number: u32) -> bool {
+        self.state.lock().await.observe(number)
+    }
+
+    /// heard is called when this server hears from a valid scheduler.
+    /// It postpones starting an election.
+    pub async fn heard(&self, number: u32) -> bool {
+        let mut state = self.state.lock().await;
+
+        let valid = state.observe(number);
+        if valid {
+            state.reset_deadline();
+        }
+
+        valid
+    }
+
+    pub async fn vote(&self, group: &GroupInfo) -> VoteResponse {
+        let mut state = self.state.lock().await;
+
+        let candidate = group.scheduler_info.id;
+        let valid = state.observe(group.number);
+
+        let granted = valid && state.voted_for.map_or(true, |id| id == candidate);
+        if granted {
+            state.voted_for = Some(candidate);
+            state.reset_deadline();
+        }
+
+        VoteResponse {
+            granted,
+            number: state.number,
+        }
+    }
+
+    /// run starts an election in which this server is a candidate, among the servers in `cluster`.
+    /// Returns the term if this server won the election.
+    pub async fn run(&self, cluster: &Cluster, this_server: &ServerInfo) -> Result<Option<u32>> {
+        // Randomize the start so that servers that detected a failure at the same time
+        // do not split the votes.
+        tokio::time::sleep(random_timeout() - ELECTION_TIMEOUT).await;
+
+        let number = {
+            let mut state = self.state.lock().await;
+            match state.start(this_server, cluster.group.number) {
+                Some(n) => n,
+                None => return Ok(None),
+            }
+        };
+
+        info!(term = number, "starting an election");
+
+        let group: Group = GroupInfo::with_number(this_server, number).into();
+
+        let requests = cluster
+            .servers
+            .iter()
+            .filter(|s| s.id != this_server.id)
+            .map(|s| request_vote(s, group.clone()));
+
+        let responses = future::join_all(requests).await;
+
+        // Vote for itself
+        let mut granted = 1;
+
+        for resp in responses {
+            match resp {
+                Ok(resp) if resp.granted => granted += 1,
+                Ok(resp) => {
+                    self.observe(resp.number).await;
+                }
+                Err(e) => warn!("failed to request vote: {e}"),
+            }
+        }
+
+        let majority = cluster.servers.len() / 2 + 1;
+
+        // Another election may have started while waiting for the votes
+        let still_candidate = self.number().await == number;
+
+        if still_candidate && granted >= majority {
+            info!(term = number, granted, "won the election");
+            Ok(Some(number))
+        } else {
+            info!(term = number, granted, "lost the election");
+            Ok(None)
+        }
+    }
+}
+
+impl ElectionState {
+    pub fn observe(&mut self, number: u32) -> bool {
+        if number > self.number {
+            self.number = number;
+            self.voted_for = None;
+        }
+
+        number >= self.number
+    }
+
+    /// start moves to the next term and votes for itself.
+    /// Returns `None` if the deadline has not come yet.
+    pub fn start(&mut self, this_server: &ServerInfo, known_number: u32) -> Option<u32> {
+        if Instant::now() < self.deadline {
+            return None;
+        }
+
+        self.number = self.number.max(known_number) + 1;
+        self.voted_for = Some(this_server.id);
+        self.reset_deadline();
+
+        Some(self.number)
+    }
+
+    pub fn reset_deadline(&mut self) {
+        self.deadline = Instant::now() + random_timeout();
+    }
+}
+
+async fn request_vote(server: &ServerInfo, group: Group) -> Result<VoteResponse> {
+    let request = async {
+        let mut client = ServerDaemonClient::connect(server.addr.clone()).await?;
+        let request = VoteRequest { group: Some(group) };
+        let resp = client.request_vote(telemetry::request(request)).await?;
+        Ok(resp.into_inner())
+    };
+
+    tokio::time::timeout(VOTE_TIMEOUT, request)
+        .await
+        .map_err(|_| format!("vote request to {:?} timed out", server.id))?
+}
+
+/// random_timeout returns a duration between `ELECTION_TIMEOUT` and
+/// `ELECTION_TIMEOUT + ELECTION_TIMEOUT_JITTER_MILLIS`.
+fn random_timeout() -> Duration {
+    let jitter = random_below(ELECTION_TIMEOUT_JITTER_MILLIS as _);
+    ELECTION_TIMEOUT + Duration::from_millis(jitter as _)
+}
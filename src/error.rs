@@ -12,7 +12,7 @@ pub enum Error {
     Mac(MacAddressError),
     NoneError,
     RequestError(Status),
-    SendStateError(mpsc::error::SendError<DaemonState>),
+    SendStateError(mpsc::error::SendError<StateCommand>),
     TransportError(TransportError),
     Text(String),
     Url(ParseError),
@@ -46,7 +46,7 @@ impl Display for Error {
 use url::ParseError;
 use Error::*;
 
-use crate::server::DaemonState;
+use crate::server::StateCommand;
 
 impl Into<Status> for Error {
     fn into(self) -> Status {
@@ -90,8 +90,8 @@ impl From<ParseError> for Error {
     }
 }
 
-impl From<mpsc::error::SendError<DaemonState>> for Error {
-    fn from(err: mpsc::error::SendError<DaemonState>) -> Self {
+impl From<mpsc::error::SendError<StateCommand>> for Error {
+    fn from(err: mpsc::error::SendError<StateCommand>) -> Self {
         SendStateError(err)
     }
 }
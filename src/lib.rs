@@ -2,8 +2,13 @@
 
 use std::result::Result as StdResult;
 
-use laqista_core::DeploymentInfo;
-use proto::{AppInstanceLocations, Deployment, Group, Server, ServerState};
+use deployment::database::SavedDeployment;
+use laqista_core::{Constraints, DeploymentInfo, Resources};
+use node::NodeInfo;
+use proto::{
+    AppInstanceLocations, CatalogEntry, Deployment, DeploymentConstraints, Group, ResourceRequest,
+    Server, ServerState,
+};
 use server::DaemonState;
 use tonic::Status;
 use utils::{get_mac, IdMap};
@@ -11,12 +16,15 @@ use uuid::Uuid;
 
 pub mod cmd;
 pub mod deployment;
+pub mod election;
 pub mod error;
 pub mod monitor;
+pub mod node;
 pub mod proxy;
 pub mod report;
 pub mod scheduler;
 pub mod server;
+pub mod telemetry;
 mod utils;
 
 pub mod proto {
@@ -30,6 +38,7 @@ pub type RpcResult<T> = StdResult<T, Status>;
 pub struct ServerInfo {
     id: Uuid,
     addr: String,
+    node: NodeInfo,
 }
 
 #[derive(Clone, Debug)]
@@ -45,6 +54,14 @@ pub struct AppInstancesInfo {
 }
 pub type AppInstanceMap = IdMap<AppInstancesInfo>;
 
+#[derive(Clone, Debug)]
+pub struct CatalogEntryInfo {
+    pub deployment: DeploymentInfo,
+    pub versions: Vec<SavedDeployment>,
+    pub desired_replicas: usize,
+}
+pub type Catalog = IdMap<CatalogEntryInfo>;
+
 impl ServerInfo {
     pub fn new(host: &str) -> Self {
         let id = Self::gen_id().unwrap();
@@ -54,7 +71,17 @@ impl ServerInfo {
 
     pub fn with_id(host: &str, id: Uuid) -> Self {
         let addr = format!("http://{}", host);
-        Self { id, addr }
+        let node = NodeInfo::default();
+        Self { id, addr, node }
+    }
+
+    pub fn with_node(mut self, node: NodeInfo) -> Self {
+        self.node = node;
+        self
+    }
+
+    pub fn node(&self) -> &NodeInfo {
+        &self.node
     }
 
     pub fn with_id_str(id: &str, host: &str) -> Result<Self> {
@@ -88,20 +115,22 @@ impl GroupInfo {
 
 impl Into<Server> for ServerInfo {
     fn into(self) -> Server {
-        let Self { id, addr } = self.clone();
+        let Self { id, addr, node } = self.clone();
         let id = id.into();
+        let node = Some(node.into());
 
-        Server { id, addr }
+        Server { id, addr, node }
     }
 }
 
 impl TryFrom<Server> for ServerInfo {
     type Error = Error;
     fn try_from(server: Server) -> Result<Self> {
-        let Server { id, addr } = server.clone();
+        let Server { id, addr, node } = server.clone();
         let id = Uuid::parse_str(&id)?;
+        let node = node.map(NodeInfo::from).unwrap_or_default();
 
-        Ok(Self { id, addr })
+        Ok(Self { id, addr, node })
     }
 }
 
@@ -150,17 +179,117 @@ impl Into<ServerState> for DaemonState {
 impl TryFrom<Deployment> for DeploymentInfo {
     type Error = Error;
     fn try_from(deployment: Deployment) -> Result<Self> {
-        let Deployment { name, source, id } = deployment;
+        let Deployment {
+            name,
+            source,
+            id,
+            resources,
+            constraints,
+        } = deployment;
         let id = Uuid::parse_str(&id)?;
-        Ok(Self { name, source, id })
+        let resources = resources.map(Resources::from).unwrap_or_default();
+        let constraints = constraints.map(Constraints::from).unwrap_or_default();
+        Ok(Self {
+            name,
+            source,
+            id,
+            resources,
+            constraints,
+        })
     }
 }
 
 impl Into<Deployment> for DeploymentInfo {
     fn into(self) -> Deployment {
-        let Self { name, source, id } = self;
+        let Self {
+            name,
+            source,
+            id,
+            resources,
+            constraints,
+        } = self;
         let id = id.to_string();
-        Deployment { name, source, id }
+        let resources = Some(resources.into());
+        let constraints = Some(constraints.into());
+        Deployment {
+            name,
+            source,
+            id,
+            resources,
+            constraints,
+        }
+    }
+}
+
+impl From<ResourceRequest> for Resources {
+    fn from(request: ResourceRequest) -> Self {
+        let ResourceRequest {
+            cpu,
+            ram,
+            gpu,
+            vram,
+        } = request;
+        Self {
+            cpu,
+            ram,
+            gpu,
+            vram,
+        }
+    }
+}
+
+impl Into<ResourceRequest> for Resources {
+    fn into(self) -> ResourceRequest {
+        let Self {
+            cpu,
+            ram,
+            gpu,
+            vram,
+        } = self;
+        ResourceRequest {
+            cpu,
+            ram,
+            gpu,
+            vram,
+        }
+    }
+}
+
+impl From<DeploymentConstraints> for Constraints {
+    fn from(constraints: DeploymentConstraints) -> Self {
+        let DeploymentConstraints {
+            gpu_required,
+            min_replicas,
+            max_replicas,
+            affinity,
+            anti_affinity,
+        } = constraints;
+        Self {
+            gpu_required,
+            min_replicas,
+            max_replicas,
+            affinity,
+            anti_affinity,
+        }
+    }
+}
+
+impl Into<DeploymentConstraints> for Constraints {
+    fn into(self) -> DeploymentConstraints {
+        let Self {
+            gpu_required,
+            min_replicas,
+            max_replicas,
+            affinity,
+            anti_affinity,
+        } = self;
+        DeploymentConstraints {
+            gpu_required,
+            min_replicas,
+            max_replicas,
+            affinity,
+            anti_affinity,
+        }
     }
 }
 
@@ -196,3 +325,51 @@ impl TryFrom<AppInstanceLocations> for AppInstancesInfo {
         })
     }
 }
+
+impl CatalogEntryInfo {
+    pub fn new(deployment: DeploymentInfo, versions: Vec<SavedDeployment>) -> Self {
+        Self {
+            deployment,
+            versions,
+            desired_replicas: 0,
+        }
+    }
+}
+
+impl Into<CatalogEntry> for CatalogEntryInfo {
+    fn into(self) -> CatalogEntry {
+        let deployment = Some(self.deployment.into());
+        let versions = self.versions.into_iter().map(|v| v.into()).collect();
+        let desired_replicas = self.desired_replicas as _;
+
+        CatalogEntry {
+            deployment,
+            versions,
+            desired_replicas,
+        }
+    }
+}
+
+impl TryFrom<CatalogEntry> for CatalogEntryInfo {
+    type Error = Error;
+    fn try_from(entry: CatalogEntry) -> Result<Self> {
+        let deployment = entry
+            .deployment
+            .ok_or("Deployment cannot be empty".to_string())?
+            .try_into()?;
+
+        let versions = entry
+            .versions
+            .into_iter()
+            .map(SavedDeployment::try_from)
+            .collect::<Result<_>>()?;
+
+        let desired_replicas = entry.desired_replicas as _;
+
+        Ok(Self {
+            deployment,
+            versions,
+            desired_replicas,
+        })
+    }
+}
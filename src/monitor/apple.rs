@@ -9,8 +9,10 @@ use chrono::{DateTime, TimeDelta, Utc};
 use plist::Date;
 use serde::{Deserialize, Serialize};
 use tokio::{sync::mpsc, task::JoinHandle};
+use tracing::{debug, warn};
 
 use crate::{
+    node::GpuInfo,
     proto::{MonitorWindow, ResourceUtilization, TimeWindow},
     utils::datetime_to_prost,
 };
@@ -41,7 +43,7 @@ pub struct DvfmState {
 impl SendMetrics for MetricsMonitor {
     fn spawn(&self, tx: mpsc::Sender<MonitorWindow>) -> JoinHandle<()> {
         tokio::spawn(async move {
-            println!("start start");
+            debug!("started the monitor");
             let commands = Self::commands();
 
             let cmd = Command::new(commands[0])
@@ -66,6 +68,28 @@ impl SendMetrics for MetricsMonitor {
     }
 }
 
+/// detect_gpu returns the GPU integrated into Apple silicon, named after the chip.
+pub fn detect_gpu() -> Option<GpuInfo> {
+    let model = sysctl("machdep.cpu.brand_string").unwrap_or_default();
+
+    Some(GpuInfo {
+        vendor: "apple".to_owned(),
+        model,
+    })
+}
+
+/// detect_ram_total returns the total memory in megabytes.
+pub fn detect_ram_total() -> Option<u64> {
+    let bytes: u64 = sysctl("hw.memsize")?.parse().ok()?;
+    Some(bytes / 1024 / 1024)
+}
+
+fn sysctl(name: &str) -> Option<String> {
+    let output = Command::new("sysctl").args(["-n", name]).output().ok()?;
+    let value = String::from_utf8(output.stdout).ok()?;
+    Some(value.trim().to_owned())
+}
+
 impl MetricsMonitor {
     pub fn new() -> Self {
         Self {}
@@ -171,8 +195,7 @@ impl<'a> Iterator for MetricsReader {
             if line == "</plist>" {
                 let parsed = self.parse(&buff);
                 if let Err(e) = &parsed {
-                    println!("WARN: failed to parse plist: {}", e);
-                    println!("last data = '{}'", line);
+                    warn!(last_line = %line, "failed to parse plist: {e}");
                 }
 
                 return parsed.ok();
@@ -1,7 +1,11 @@
 pub mod radeon;
 pub use radeon::*;
+use std::fs;
+
 use tokio::{sync::mpsc::Sender, task::JoinHandle};
 
+use crate::node::GpuInfo;
+
 use super::SendMetrics;
 
 mod parse;
@@ -20,7 +24,8 @@ impl SendMetrics for MetricsMonitor {
             use HostSystem::*;
 
             match HostSystem::determine() {
-                Radeon => {
+                // radeontop is the only monitor implemented for Linux
+                Radeon | Unknown => {
                     let monitor = RadeonMonitor::new();
                     monitor.run(tx).await;
                 }
@@ -37,9 +42,69 @@ enum HostSystem {
     Unknown,
 }
 
+const DRM_DIR: &str = "/sys/class/drm";
+
 impl HostSystem {
     pub fn determine() -> Self {
-        //TODO: Deteremine underlying system
-        Self::Radeon
+        Self::probe().map_or(Self::Unknown, |(system, _)| system)
+    }
+
+    /// probe returns the vendor of the first GPU, with its model if the driver exposes it.
+    fn probe() -> Option<(Self, String)> {
+        for entry in fs::read_dir(DRM_DIR).ok()?.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+
+            // Skip connectors, such as `card0-DP-1`
+            if !name.starts_with("card") || name.contains('-') {
+                continue;
+            }
+
+            let device = entry.path().join("device");
+            let Ok(vendor) = fs::read_to_string(device.join("vendor")) else {
+                continue;
+            };
+
+            let system = match vendor.trim() {
+                "0x1002" => Self::Radeon,
+                "0x10de" => Self::Nvidia,
+                _ => continue,
+            };
+
+            let model = fs::read_to_string(device.join("product_name"))
+                .or_else(|_| fs::read_to_string(device.join("device")))
+                .map(|m| m.trim().to_owned())
+                .unwrap_or_default();
+
+            return Some((system, model));
+        }
+
+        None
     }
 }
+
+pub fn detect_gpu() -> Option<GpuInfo> {
+    let (system, model) = HostSystem::probe()?;
+
+    let vendor = match system {
+        HostSystem::Radeon => "amd",
+        HostSystem::Nvidia => "nvidia",
+        HostSystem::Unknown => return None,
+    };
+
+    Some(GpuInfo {
+        vendor: vendor.to_owned(),
+        model,
+    })
+}
+
+/// detect_ram_total returns the total memory in megabytes.
+pub fn detect_ram_total() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+
+    // MemTotal:       16318480 kB
+    let line = meminfo.lines().find(|l| l.starts_with("MemTotal:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kb / 1024)
+}
@@ -5,6 +5,7 @@ use std::{
 
 use chrono::{DateTime, TimeDelta, Utc};
 use tokio::sync::mpsc;
+use tracing::{debug, error, warn};
 
 use crate::{
     proto::{MonitorWindow, ResourceUtilization, TimeWindow},
@@ -25,7 +26,7 @@ impl RadeonMonitor {
     }
 
     pub async fn run(&self, tx: mpsc::Sender<MonitorWindow>) -> ! {
-        println!("start start");
+        debug!("started the monitor");
         let commands = Self::commands();
 
         let cmd = Command::new(commands[0])
@@ -113,7 +114,7 @@ impl MetricsReader {
 
     pub fn skip_header(&mut self) -> String {
         if self.seen_header {
-            println!("WARN: MetricsReader.skip_header(): we have already seen a header");
+            warn!("MetricsReader.skip_header(): we have already seen a header");
         }
 
         let line = self.next_inner().expect("EOF");
@@ -130,7 +131,7 @@ impl MetricsReader {
             .unwrap_or(Err(IOError::other("unexpected end of lines")));
 
         read_result
-            .map_err(|e| println!("ERR: MetricsReader.next_inner(): failed to read line: {e}"))
+            .map_err(|e| error!("MetricsReader.next_inner(): failed to read line: {e}"))
             .ok()
     }
 }
@@ -140,7 +141,7 @@ impl Iterator for MetricsReader {
     fn next(&mut self) -> Option<Self::Item> {
         let line = self.next_inner()?;
         let (_, metrics) = metrics_line(&line)
-            .map_err(|e| println!("ERR: MetricsReader.next(): failed to parse: {e}"))
+            .map_err(|e| error!("MetricsReader.next(): failed to parse: {e}"))
             .ok()?;
 
         Some(metrics)
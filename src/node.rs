@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+
+use laqista_core::Constraints;
+
+use crate::monitor::{detect_gpu, detect_ram_total};
+use crate::proto::{Gpu, Node, NodeCapabilities};
+
+/// NodeInfo describes a server, by the labels given by the operator and its hardware.
+/// Daemons report it at Join, so that the scheduler can filter servers for a deployment.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NodeInfo {
+    pub labels: HashMap<String, String>,
+    pub capabilities: Capabilities,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub cpu_cores: u32,
+    /// In megabytes
+    pub ram_total: u64,
+    pub gpu: Option<GpuInfo>,
+    pub wasm: bool,
+    pub onnx_backends: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GpuInfo {
+    pub vendor: String,
+    pub model: String,
+}
+
+impl NodeInfo {
+    pub fn new(labels: HashMap<String, String>, capabilities: Capabilities) -> Self {
+        Self {
+            labels,
+            capabilities,
+        }
+    }
+
+    /// detect returns the information of this host.
+    pub fn detect(labels: HashMap<String, String>) -> Self {
+        Self::new(labels, Capabilities::detect())
+    }
+
+    /// satisfies returns whether an instance under `constraints` can run on this node.
+    pub fn satisfies(&self, constraints: &Constraints) -> bool {
+        let has_label = |(k, v): (&String, &String)| self.labels.get(k) == Some(v);
+
+        if constraints.gpu_required && self.capabilities.gpu.is_none() {
+            return false;
+        }
+
+        constraints.affinity.iter().all(has_label)
+            && !constraints.anti_affinity.iter().any(has_label)
+    }
+}
+
+impl Capabilities {
+    pub fn detect() -> Self {
+        let cpu_cores = std::thread::available_parallelism().map_or(0, |n| n.get() as _);
+        let ram_total = detect_ram_total().unwrap_or(0);
+        let gpu = detect_gpu();
+
+        // wonnx runs ONNX models on the GPU, while WASM modules run anywhere
+        let onnx_backends = match gpu {
+            Some(_) => vec!["wonnx".to_owned()],
+            None => vec![],
+        };
+
+        Self {
+            cpu_cores,
+            ram_total,
+            gpu,
+            wasm: true,
+            onnx_backends,
+        }
+    }
+}
+
+/// parse_label parses `key=value` given on the command line.
+pub fn parse_label(s: &str) -> std::result::Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or(format!("label must be in the form of key=value: {s}"))?;
+
+    if key.is_empty() {
+        return Err(format!("label key cannot be empty: {s}"));
+    }
+
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+impl Into<Node> for NodeInfo {
+    fn into(self) -> Node {
+        let Self {
+            labels,
+            capabilities,
+        } = self;
+        let capabilities = Some(capabilities.into());
+
+        Node {
+            labels,
+            capabilities,
+        }
+    }
+}
+
+impl From<Node> for NodeInfo {
+    fn from(node: Node) -> Self {
+        let Node {
+            labels,
+            capabilities,
+        } = node;
+        let capabilities = capabilities.map(Capabilities::from).unwrap_or_default();
+
+        Self {
+            labels,
+            capabilities,
+        }
+    }
+}
+
+impl Into<NodeCapabilities> for Capabilities {
+    fn into(self) -> NodeCapabilities {
+        let Self {
+            cpu_cores,
+            ram_total,
+            gpu,
+            wasm,
+            onnx_backends,
+        } = self;
+        let gpu = gpu.map(|GpuInfo { vendor, model }| Gpu { vendor, model });
+
+        NodeCapabilities {
+            cpu_cores,
+            ram_total,
+            gpu,
+            wasm,
+            onnx_backends,
+        }
+    }
+}
+
+impl From<NodeCapabilities> for Capabilities {
+    fn from(capabilities: NodeCapabilities) -> Self {
+        let NodeCapabilities {
+            cpu_cores,
+            ram_total,
+            gpu,
+            wasm,
+            onnx_backends,
+        } = capabilities;
+        let gpu = gpu.map(|Gpu { vendor, model }| GpuInfo { vendor, model });
+
+        Self {
+            cpu_cores,
+            ram_total,
+            gpu,
+            wasm,
+            onnx_backends,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn labels(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_satisfies() {
+        let node = NodeInfo::new(
+            labels(&[("zone", "a"), ("disk", "ssd")]),
+            Capabilities::default(),
+        );
+
+        let mut constraints = Constraints {
+            affinity: labels(&[("zone", "a")]),
+            ..Default::default()
+        };
+        assert!(node.satisfies(&constraints));
+
+        constraints.anti_affinity = labels(&[("disk", "ssd")]);
+        assert!(!node.satisfies(&constraints));
+
+        constraints.anti_affinity = labels(&[("disk", "hdd")]);
+        assert!(node.satisfies(&constraints));
+
+        // No GPU is reported
+        constraints.gpu_required = true;
+        assert!(!node.satisfies(&constraints));
+    }
+
+    #[test]
+    fn test_parse_label() {
+        assert_eq!(
+            parse_label("zone=a=b").unwrap(),
+            ("zone".to_owned(), "a=b".to_owned())
+        );
+        assert!(parse_label("zone").is_err());
+        assert!(parse_label("=a").is_err());
+    }
+}
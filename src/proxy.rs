@@ -5,6 +5,7 @@ use h2::client;
 use http_body::Body;
 use hyper::{HeaderMap, Request, Uri};
 use tokio::net::TcpStream;
+use tracing::{debug, error};
 
 pub async fn create_reverse_proxy(
     package: &str,
@@ -14,7 +15,7 @@ pub async fn create_reverse_proxy(
     let addr = addr.to_owned();
 
     let handler: MethodRouter = any(|req: Request<hyper::body::Body>| async move {
-        println!("proxying new request to {}", &addr);
+        debug!(%addr, "proxying new request");
 
         let tcp = TcpStream::connect(addr)
             .await
@@ -46,7 +47,7 @@ pub async fn create_reverse_proxy(
 
         let (resp, mut stream) = client
             .send_request(req, false)
-            .map_err(|e| println!("failed to send request to destination: {}", e))
+            .map_err(|e| error!("failed to send request to destination: {e}"))
             .unwrap();
 
         // stream.send_data(body, true).expect("failed to send data");
@@ -58,7 +59,7 @@ pub async fn create_reverse_proxy(
 
         tokio::spawn(async move {
             if let Err(e) = h2.await {
-                println!("GOT ERR={:?}", e);
+                error!("connection to the destination failed: {e:?}");
             }
         });
 
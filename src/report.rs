@@ -2,15 +2,22 @@ use std::error::Error;
 
 use tokio::{select, sync::mpsc, task::JoinHandle};
 use tokio_util::sync::CancellationToken;
+use tonic::Code;
+use tracing::{debug, error, info, warn};
 
 use crate::{
+    election::Election,
     error::Error as LaqistaError,
     monitor::{MetricsMonitor, SendMetrics},
     proto::{scheduler_client::SchedulerClient, ClusterState, MonitorWindow, ReportRequest},
     scheduler::Cluster,
-    server::{DaemonState, StateCommand, StateSender},
+    server::{
+        admission::AdmissionControl, latency::LatencyRecorder, metrics::Metrics, DaemonState,
+        StateCommand, StateSender,
+    },
+    telemetry,
     utils::cluster_differs,
-    ServerInfo,
+    GroupInfo, ServerInfo,
 };
 
 pub struct MetricsReporter {
@@ -18,12 +25,24 @@ pub struct MetricsReporter {
     server: ServerInfo,
     last_cluster_state: Option<ClusterState>,
     state_tx: StateSender,
+    election: Election,
+    latency: LatencyRecorder,
+    admission: AdmissionControl,
+    metrics: Metrics,
     rx: mpsc::Receiver<MonitorWindow>,
     sender_handle: JoinHandle<()>,
 }
 
 impl MetricsReporter {
-    pub fn new(state_tx: StateSender, server: ServerInfo, scheduler: ServerInfo) -> Self {
+    pub fn new(
+        state_tx: StateSender,
+        election: Election,
+        latency: LatencyRecorder,
+        admission: AdmissionControl,
+        metrics: Metrics,
+        server: ServerInfo,
+        scheduler: ServerInfo,
+    ) -> Self {
         let (tx, rx) = mpsc::channel(1);
 
         let sender: Box<dyn SendMetrics> = Box::new(MetricsMonitor::new());
@@ -34,13 +53,17 @@ impl MetricsReporter {
             server,
             last_cluster_state: None,
             state_tx,
+            election,
+            latency,
+            admission,
+            metrics,
             rx,
             sender_handle: monitor_handle,
         }
     }
 
     pub async fn start(&mut self, token: CancellationToken) {
-        println!("start listen thread");
+        debug!(scheduler = %self.scheduler.id, "started the reporter");
 
         loop {
             select! {
@@ -48,10 +71,11 @@ impl MetricsReporter {
 
                     self.report(&window.into())
                         .await
-                        .expect("failed to report metrics");
+                        .err()
+                        .map(|e| error!("failed to report metrics: {e}"));
                 }
                 _ = token.cancelled() => {
-                    println!("cancelled");
+                    debug!("stopped the reporter");
                     self.stop();
                     break;
                 }
@@ -64,60 +88,102 @@ impl MetricsReporter {
     }
 
     pub async fn report(&mut self, metrics: &MonitorWindow) -> Result<(), Box<dyn Error>> {
-        let mut client = SchedulerClient::connect(self.scheduler.addr.clone()).await?;
-
         let server = Some(self.server.clone().into());
 
+        if let Some(utilization) = &metrics.utilization {
+            self.metrics.observe_utilization(utilization);
+        }
+
         let windows = vec![metrics.clone().into()];
 
-        let req = ReportRequest { windows, server };
+        let latencies = self.latency.drain();
+        let queues = self.admission.drain();
 
-        let report_result = client.report(req).await;
+        let req = ReportRequest {
+            windows,
+            server,
+            latencies,
+            queues,
+        };
+
+        let report_result = match SchedulerClient::connect(self.scheduler.addr.clone()).await {
+            Ok(mut client) => client
+                .report(telemetry::request(req))
+                .await
+                .map_err(LaqistaError::from),
+            Err(e) => Err(e.into()),
+        };
+
+        if report_result.is_err() {
+            self.metrics.observe_report_failure();
+        }
 
         match report_result {
             Ok(resp) => {
                 let inner = resp.into_inner();
+
+                let number = inner
+                    .cluster
+                    .as_ref()
+                    .and_then(|c| c.group.as_ref())
+                    .map(|g| g.number)
+                    .ok_or("Group in response cannot be empty")?;
+
+                // Reject the response from a stale scheduler, and elect a new one
+                if !self.election.heard(number).await {
+                    warn!(group = number, "scheduler is stale. Starting an election.");
+                    return self.elect().await;
+                }
+
+                if let Some(cluster) = &inner.cluster {
+                    self.metrics.observe_cluster(cluster);
+                }
                 self.put_cluster(inner.cluster);
                 Ok(())
             }
-            Err(s) => {
-                let err: LaqistaError = s.into();
-                match err {
-                    LaqistaError::TransportError(te) => {
-                        println!("MetricsReporter::report: Failed to report to the server: {te}");
-
-                        let cluster_result = self
-                            .last_cluster_state
-                            .clone()
-                            .ok_or("No latest cluster state is saved")?
-                            .try_into();
-
-                        let mut cluster: Cluster = match cluster_result {
-                            Ok(cluster) => cluster,
-                            Err(e) => return Err(Box::new(e)),
-                        };
-
-                        let id = cluster.group.scheduler_info.id.clone();
-                        cluster.remove_server(&id);
+            Err(LaqistaError::TransportError(te)) => {
+                warn!(scheduler = %self.scheduler.id, "failed to report to the scheduler: {te}");
+                self.elect().await
+            }
+            Err(LaqistaError::RequestError(s)) if s.code() == Code::NotFound => {
+                // The scheduler has removed this server, e.g., as it was considered dead
+                warn!("not a member of the cluster. Joining again.");
+                let state = DaemonState::Joining(self.scheduler.addr.clone());
+                self.state_tx.send(StateCommand::Update(state)).await?;
+                Ok(())
+            }
+            Err(err) => Err(err)?,
+        }
+    }
 
-                        let next_scheduler = cluster.choose_scheduler();
+    /// elect runs an election to choose the next scheduler, among the latest cluster members.
+    /// If this server wins, it becomes the scheduler. Otherwise, the winner will notify us.
+    async fn elect(&mut self) -> Result<(), Box<dyn Error>> {
+        let cluster_result = self
+            .last_cluster_state
+            .clone()
+            .ok_or("No latest cluster state is saved")?
+            .try_into();
+
+        let mut cluster: Cluster = match cluster_result {
+            Ok(cluster) => cluster,
+            Err(e) => return Err(Box::new(e)),
+        };
 
-                        let state_command = if next_scheduler.id == self.server.id {
-                            StateCommand::BecomeScheduler(cluster)
-                        } else {
-                            let state = DaemonState::Joining(next_scheduler.addr.clone());
-                            StateCommand::Update(state)
-                        };
+        let number = match self.election.run(&cluster, &self.server).await? {
+            Some(number) => number,
+            None => return Ok(()),
+        };
 
-                        self.state_tx.send(state_command).await?;
+        let id = cluster.group.scheduler_info.id.clone();
+        cluster.remove_server(&id);
+        cluster.group = GroupInfo::with_number(&self.server, number);
 
-                        Ok(())
-                    }
+        self.state_tx
+            .send(StateCommand::BecomeScheduler(cluster))
+            .await?;
 
-                    err => Err(err)?,
-                }
-            }
-        }
+        Ok(())
     }
 
     fn put_cluster(&mut self, current: Option<ClusterState>) -> bool {
@@ -128,7 +194,8 @@ impl MetricsReporter {
         };
 
         if changed {
-            println!("Cluster state updated.\n{:?}", &current);
+            info!("cluster state updated");
+            debug!(cluster = ?current);
             self.last_cluster_state = current;
         }
 
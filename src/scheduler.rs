@@ -1,31 +1,66 @@
+pub mod audit;
+pub mod execution;
+pub mod forecast;
+pub mod health;
 pub mod interface;
 pub mod mean;
+pub mod placement;
+pub mod power_of_two;
+pub mod qos;
+pub mod registry;
+pub mod round_robin;
+pub mod scale_out;
+pub mod simulation;
 pub mod stats;
+pub mod store;
 
 use std::borrow::BorrowMut;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use laqista_core::{Constraints, Resources};
+use tokio::select;
 use tokio::sync::Mutex;
+use tokio::task::spawn_blocking;
+use tokio_util::sync::CancellationToken;
 use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::deployment::database::DeploymentDatabase;
+use crate::deployment::database::{DeploymentDatabase, SavedDeployment};
 use crate::proto::scheduler_server::Scheduler;
 use crate::proto::server_daemon_client::ServerDaemonClient;
 use crate::proto::{
-    ClusterState, DeployRequest, DeployResponse, Deployment, JoinRequest, JoinResponse,
-    LookupRequest, LookupResponse, Nomination, ReportRequest, ReportResponse, Server, SpawnRequest,
-    SpawnResponse,
+    Accuracy, ClusterState, DecisionKind, DeployRequest, DeployResponse, Deployment, DestroyReason,
+    DestroyRequest, DestroyResponse, ExecutionMode, ExplainRequest, ExplainResponse,
+    GetInfoRequest, JoinRequest, JoinResponse, LeaveRequest, LeaveResponse, ListDecisionsRequest,
+    ListDecisionsResponse, LookupRequest, LookupResponse, NominateRequest, Nomination,
+    NotifyRequest, ReportRequest, ReportResponse, Server, SpawnRequest, SpawnResponse,
 };
-use crate::server::{DaemonState, StateSender};
+use crate::server::metrics::Metrics;
+use crate::server::{DaemonState, StateCommand, StateSender};
+use crate::telemetry;
 use crate::utils::IdMap;
-use crate::{AppInstanceMap, AppInstancesInfo, DeploymentInfo, GroupInfo, RpcResult, ServerInfo};
+use crate::{
+    AppInstanceMap, AppInstancesInfo, Catalog, CatalogEntryInfo, DeploymentInfo, GroupInfo,
+    RpcResult, ServerInfo,
+};
 use crate::{Error, Result};
 
+use self::audit::{DecisionLog, DecisionRecord};
+use self::execution::{select_execution, ExecutionConfig};
+use self::forecast::{ForecastConfig, RateForecaster};
+use self::health::{FailureDetector, FailureDetectorConfig, Liveness};
 use self::interface::DeploymentScheduler;
-use self::stats::{ServerStats, StatsMap};
+use self::mean::SCALEOUT_THREASHOLD;
+use self::placement::{BestFitPlacement, ServerCapacity};
+use self::qos::QosInfo;
+use self::registry::PolicyInfo;
+use self::scale_out::{ScaleOutConfig, ScaleOutTracker};
+use self::stats::{LatencyStats, QueueStats, ServerStats, StatsMap};
+use self::store::{StateOp, StateStore};
 
 #[derive(Debug)]
 pub struct AuthoritativeScheduler {
@@ -37,8 +72,35 @@ pub struct AuthoritativeScheduler {
 pub struct SchedulerRuntime {
     pub cluster: Cluster,
     pub scheduler: Box<dyn DeploymentScheduler>,
-    pub deployments: IdMap<DeploymentInfo>,
     pub database: DeploymentDatabase,
+    pub scale_in: ScaleInConfig,
+    /// When lookups steer requests from GPUs to CPUs
+    pub execution: ExecutionConfig,
+    /// Since when all instances of each deployment have been underutilized
+    pub underutilized_since: IdMap<Instant>,
+    pub health: FailureDetector,
+    /// Places instances of the deployments that declare their resources
+    pub placement: BestFitPlacement,
+    /// Request rates of the deployments, to scale out ahead of the load
+    pub forecast: RateForecaster,
+    /// Scale-outs in flight, so that each deployment has at most one at a time
+    pub scale_out: ScaleOutTracker,
+    /// Latest lookups and placements, for explaining them later
+    pub decisions: DecisionLog,
+    /// Shared with the daemon, which exposes them over HTTP
+    pub metrics: Metrics,
+    /// Persists the cluster. `None` if the state could not be stored on disk.
+    pub store: Option<StateStore>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScaleInConfig {
+    /// How often the scheduler looks for deployments to scale in
+    pub interval: Duration,
+    /// How long a deployment must stay underutilized before it is scaled in
+    pub window: Duration,
+    /// Number of instances that are kept regardless of utilization
+    pub min_replicas: usize,
 }
 
 #[derive(Clone, Debug)]
@@ -46,7 +108,9 @@ pub struct Cluster {
     pub group: GroupInfo,
     pub servers: Vec<ServerInfo>,
     pub instances: AppInstanceMap,
+    pub catalog: Catalog,
     pub server_stats: StatsMap,
+    pub policy: PolicyInfo,
 }
 
 impl AuthoritativeScheduler {
@@ -56,11 +120,25 @@ impl AuthoritativeScheduler {
         tx: StateSender,
         database: DeploymentDatabase,
     ) -> Self {
+        let store = StateStore::open(database.root())
+            .and_then(|store| store.snapshot(&cluster).map(|_| store))
+            .map_err(|e| warn!("scheduler state will not be persisted: {e}"))
+            .ok();
+
         let runtime = Arc::new(Mutex::new(SchedulerRuntime {
             cluster,
             scheduler,
-            deployments: IdMap::new(),
             database,
+            scale_in: ScaleInConfig::default(),
+            execution: ExecutionConfig::default(),
+            underutilized_since: IdMap::new(),
+            health: FailureDetector::new(FailureDetectorConfig::default()),
+            placement: BestFitPlacement::new(),
+            forecast: RateForecaster::new(ForecastConfig::default()),
+            scale_out: ScaleOutTracker::new(ScaleOutConfig::default()),
+            decisions: DecisionLog::default(),
+            metrics: Metrics::new(),
+            store,
         }));
 
         let tx = Arc::new(Mutex::new(tx));
@@ -68,103 +146,540 @@ impl AuthoritativeScheduler {
         Self { runtime, tx }
     }
 
+    /// from_cluster creates the scheduler with the policy of the cluster.
+    pub fn from_cluster(
+        cluster: Cluster,
+        tx: StateSender,
+        database: DeploymentDatabase,
+    ) -> Result<Self> {
+        let scheduler = cluster.policy.create()?;
+        Ok(Self::new(cluster, scheduler, tx, database))
+    }
+
+    /// from_server creates the scheduler of a new cluster, or of the cluster restored from disk.
+    /// `policy` overrides the one of the restored cluster.
     pub fn from_server(
         server: &ServerInfo,
-        scheduler: Box<dyn DeploymentScheduler>,
+        policy: PolicyInfo,
         tx: StateSender,
         database: DeploymentDatabase,
-    ) -> Self {
-        let cluster = Cluster::new(server);
+    ) -> Result<Self> {
+        let mut cluster =
+            Self::restore_cluster(server, &database).unwrap_or_else(|| Cluster::new(server));
+        cluster.policy = policy;
+
+        Self::from_cluster(cluster, tx, database)
+    }
 
-        Self::new(cluster, scheduler, tx, database)
+    /// restore_cluster loads the cluster persisted by the previous run of the scheduler.
+    fn restore_cluster(server: &ServerInfo, database: &DeploymentDatabase) -> Option<Cluster> {
+        let loaded = StateStore::open(database.root()).and_then(|store| store.load());
+
+        match loaded {
+            Ok(Some(cluster)) => {
+                info!(
+                    group = cluster.group.number,
+                    servers = cluster.servers.len(),
+                    "restored the cluster"
+                );
+                Some(cluster.restored_by(server))
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("failed to restore the scheduler state: {e}");
+                None
+            }
+        }
     }
 
-    pub async fn push_server(&self, server: ServerInfo) {
-        self.runtime.lock().await.cluster.servers.push(server);
+    pub async fn push_server(&self, server: ServerInfo) -> Result<()> {
+        self.runtime.lock().await.apply(StateOp::Join(server)).await
     }
 
     pub async fn deploy_in_us(&self, deployment: DeploymentInfo) -> Result<SpawnResponse> {
-        let request = SpawnRequest {
+        let mut request = SpawnRequest {
             deployment: Some(deployment.clone().into()),
+            group: None,
         };
 
         let target_server = {
-            println!("taking lock of runtime to get scheduler and stats");
-            let runtime = self.runtime.lock().await;
-            println!("took lock");
+            let mut runtime = self.runtime.lock().await;
 
-            runtime
-                .scheduler
-                .schedule(&runtime.cluster.server_stats)
-                .unwrap_or({
-                    println!("WARN: failed to schedule. Using the first server");
-                    runtime.cluster.servers[0].clone()
-                })
+            request.group = Some(runtime.cluster.group.clone().into());
+
+            let count = runtime.cluster.instance_count(&deployment.id);
+            if !deployment.constraints.replicas_allowed(count + 1) {
+                Err(format!(
+                    "Deployment {:?} already has the maximum number of replicas",
+                    deployment.id
+                ))?;
+            }
+
+            let placed = runtime.place_instance(&deployment);
+            runtime.record_placement(&deployment, &placed);
+            placed?
         };
-        println!("got target server = {:?}", &target_server);
+        debug!(deployment_id = %deployment.id, server_id = %target_server.id, "placed an instance");
 
         let mut client = self.client(&target_server).await?;
 
-        let request = Request::new(request);
+        let request = telemetry::request(request);
 
         let response = client.spawn(request).await?;
         if !response.get_ref().success {
             return Err("Unsuccessful spawn".into());
         }
 
-        println!("spawend successfully");
-
         // Update deployments information and instances information atomicly
         {
-            println!("taking lock of runtime");
             let mut runtime = self.runtime.lock().await;
-            runtime
-                .deployments
-                .0
-                .insert(deployment.id, deployment.clone());
+            if !runtime.cluster.catalog.0.contains_key(&deployment.id) {
+                let entry = CatalogEntryInfo::new(deployment.clone(), vec![]);
+                runtime.apply(StateOp::PutDeployment(entry)).await?;
+            }
 
             runtime
-                .cluster
-                .insert_instance(deployment, vec![target_server]);
-            println!("freeing runtime");
+                .apply(StateOp::AddInstance(
+                    deployment.clone(),
+                    target_server.clone(),
+                ))
+                .await?;
         }
 
-        println!(
-            "Successfully spawned app ({:?}) on {:?}",
-            response.get_ref().deployment,
-            response.get_ref().server,
-        );
+        info!(deployment_id = %deployment.id, server_id = %target_server.id, "spawned an instance");
 
         Ok(response.into_inner())
     }
 
-    pub async fn handle_failed_server<T>(
+    pub async fn destroy_instance(
         &self,
-        result: Result<T>,
-        server: &Server,
-    ) -> RpcResult<Option<DaemonState>> {
-        let e = match result {
-            Err(Error::TransportError(e)) => e,
-            Err(e) => return Err(e.into()),
-            _ => return Ok(None),
+        deployment_id: &Uuid,
+        target_server: &ServerInfo,
+        reason: DestroyReason,
+    ) -> Result<DestroyResponse> {
+        let group = {
+            let runtime = self.runtime.lock().await;
+            let hosting = runtime
+                .cluster
+                .deployments_on(&target_server.id)
+                .iter()
+                .any(|d| &d.id == deployment_id);
+            if !hosting {
+                Err(format!(
+                    "Instance of {deployment_id} not found on {:?}",
+                    target_server.id
+                ))?;
+            }
+
+            // Other replicas would keep being routed without the deployment in the catalog
+            if reason == DestroyReason::Removed && runtime.cluster.instance_count(deployment_id) > 1
+            {
+                Err(format!(
+                    "Deployment {deployment_id} has other replicas. Scale it in first."
+                ))?;
+            }
+
+            runtime.cluster.group.clone()
         };
 
-        println!(
-            "Server {:?} has failed with following error: {e:?}",
-            server.id
-        );
+        let mut request = DestroyRequest {
+            app_id: deployment_id.to_string(),
+            group: Some(group.into()),
+            ..Default::default()
+        };
+        request.set_reason(reason);
 
-        let id =
-            Uuid::try_parse(&server.id).map_err(|e| <Error as Into<Status>>::into(e.into()))?;
+        let mut client = self.client(target_server).await?;
 
-        let mut runtime = self.runtime.lock().await;
+        let response = client.destroy(telemetry::request(request)).await?;
+        if !response.get_ref().success {
+            return Err("Unsuccessful destroy".into());
+        }
 
-        let maybe_removed = runtime.cluster.remove_server(&id);
-        if maybe_removed.is_none() {
-            println!("WARN: failed to remove the server from list: {server:?}");
+        // Stop routing only once the instance is gone, so that a failed destroy keeps it usable
+        {
+            let mut runtime = self.runtime.lock().await;
+            runtime
+                .apply(StateOp::RemoveInstance(*deployment_id, target_server.id))
+                .await?;
+
+            match reason {
+                DestroyReason::Removed => {
+                    runtime
+                        .apply(StateOp::RemoveDeployment(*deployment_id))
+                        .await?;
+                    runtime.forecast.remove(deployment_id);
+                    runtime.scale_out.remove(deployment_id);
+                }
+                _ => {
+                    let count = runtime.cluster.instance_count(deployment_id);
+                    if let Some(entry) = runtime.cluster.catalog.0.get(deployment_id) {
+                        let entry = CatalogEntryInfo {
+                            desired_replicas: count,
+                            ..entry.clone()
+                        };
+                        runtime.apply(StateOp::PutDeployment(entry)).await?;
+                    }
+                }
+            }
         }
 
-        Ok(None)
+        info!(%deployment_id, server_id = %target_server.id, "destroyed an instance");
+
+        Ok(response.into_inner())
+    }
+
+    /// start_scale_in spawns a task that periodically destroys instances of underutilized
+    /// deployments. The task runs until the returned token is cancelled.
+    pub async fn start_scale_in(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+
+        let interval = self.runtime.lock().await.scale_in.interval;
+        let this = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = tokio::time::sleep(interval) => this.scale_in().await,
+                    _ = cloned.cancelled() => break,
+                }
+            }
+        });
+
+        token
+    }
+
+    pub async fn scale_in(&self) {
+        let targets = self.runtime.lock().await.scale_in_targets(Instant::now());
+
+        for (deployment_id, server) in targets {
+            info!(%deployment_id, server_id = %server.id, "scaling in");
+
+            self.destroy_instance(&deployment_id, &server, DestroyReason::ScaleIn)
+                .await
+                .err()
+                .map(|e| error!("destroy_instance failed: {e}"));
+        }
+    }
+
+    /// start_autoscaler spawns a task that periodically spawns instances of the deployments
+    /// whose load is forecasted to exceed the threshold. The task runs until the returned token
+    /// is cancelled.
+    pub async fn start_autoscaler(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+
+        let interval = self.runtime.lock().await.forecast.config.interval;
+        let this = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = tokio::time::sleep(interval) => this.scale_out_predicted().await,
+                    _ = cloned.cancelled() => break,
+                }
+            }
+        });
+
+        token
+    }
+
+    pub async fn scale_out_predicted(&self) {
+        let targets = self
+            .runtime
+            .lock()
+            .await
+            .predicted_scale_out_targets(Instant::now());
+
+        for (deployment, began) in targets {
+            info!(deployment_id = %deployment.id, "scaling out ahead of the forecasted load");
+
+            self.scale_out(deployment, began).await;
+        }
+    }
+
+    /// scale_out spawns an instance of the deployment, whose scale-out must have begun at
+    /// `began` by `SchedulerRuntime::begin_scale_out`.
+    pub async fn scale_out(&self, deployment: DeploymentInfo, began: Instant) {
+        let id = deployment.id;
+
+        self.deploy_in_us(deployment)
+            .await
+            .err()
+            .map(|e| error!(deployment_id = %id, "deploy_in_us failed: {e}"));
+
+        self.runtime
+            .lock()
+            .await
+            .scale_out
+            .end(id, began, Instant::now());
+    }
+
+    /// nominate hands the authority over to `nominee`, and then this server demotes itself.
+    pub async fn nominate(&self, nominee: &ServerInfo) -> Result<GroupInfo> {
+        let group = self.hand_over(nominee).await?;
+
+        self.tx
+            .lock()
+            .await
+            .send(StateCommand::Update(DaemonState::Running(group.clone())))
+            .await?;
+
+        Ok(group)
+    }
+
+    /// hand_over makes `nominee` the scheduler of the next group, and tells other members.
+    /// Unlike `nominate`, this server keeps its state.
+    pub async fn hand_over(&self, nominee: &ServerInfo) -> Result<GroupInfo> {
+        let (cluster, this_id) = {
+            let runtime = self.runtime.lock().await;
+            let this_id = runtime.cluster.group.scheduler_info.id;
+            (runtime.cluster.handed_over(nominee), this_id)
+        };
+
+        if nominee.id == this_id {
+            return Err("Cannot nominate the current scheduler".into());
+        }
+
+        let request = NominateRequest {
+            nomination: Some(cluster.to_nomination()),
+        };
+
+        let mut client = self.client(nominee).await?;
+        let response = client.nominate(telemetry::request(request)).await?;
+        if !response.get_ref().success {
+            return Err("Unsuccessful nomination".into());
+        }
+
+        info!(nominee = %nominee.id, group = cluster.group.number, "nominated the new scheduler");
+
+        self.notify_members(&cluster, &[this_id, nominee.id]).await;
+
+        Ok(cluster.group)
+    }
+
+    /// successor returns the least loaded member other than this server.
+    pub async fn successor(&self) -> Option<ServerInfo> {
+        let runtime = self.runtime.lock().await;
+        let this_id = runtime.cluster.group.scheduler_info.id;
+
+        let ids: Vec<_> = runtime
+            .cluster
+            .servers
+            .iter()
+            .map(|s| s.id)
+            .filter(|id| id != &this_id)
+            .collect();
+        let stats_map = runtime.cluster.server_stats.clone_by_ids(&ids);
+
+        stats::least_utilized(&stats_map).or_else(|| {
+            runtime
+                .cluster
+                .servers
+                .iter()
+                .find(|s| s.id != this_id)
+                .cloned()
+        })
+    }
+
+    /// announce tells every member that this server is the scheduler of the current group.
+    pub async fn announce(&self) {
+        let cluster = self.runtime.lock().await.cluster.clone();
+        let this_id = cluster.group.scheduler_info.id;
+
+        self.notify_members(&cluster, &[this_id]).await;
+    }
+
+    async fn notify_members(&self, cluster: &Cluster, except: &[Uuid]) {
+        let state: ClusterState = cluster.clone().into();
+
+        for server in &cluster.servers {
+            if except.contains(&server.id) {
+                continue;
+            }
+
+            let request = NotifyRequest {
+                cluster: Some(state.clone()),
+            };
+
+            let result = match self.client(server).await {
+                Ok(mut client) => client
+                    .notify(telemetry::request(request))
+                    .await
+                    .map_err(Error::from),
+                Err(e) => Err(e),
+            };
+
+            result
+                .err()
+                .map(|e| warn!(server_id = %server.id, "failed to notify: {e}"));
+        }
+    }
+
+    /// start_failure_detector spawns a task that periodically pings silent servers, and removes
+    /// dead ones from the cluster. The task runs until the returned token is cancelled.
+    pub async fn start_failure_detector(&self) -> CancellationToken {
+        let token = CancellationToken::new();
+        let cloned = token.clone();
+
+        let interval = self.runtime.lock().await.health.config.interval;
+        let this = self.clone();
+
+        tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = tokio::time::sleep(interval) => {
+                        this.detect_failures().await;
+                        this.replace_lost_instances().await;
+                    }
+                    _ = cloned.cancelled() => break,
+                }
+            }
+        });
+
+        token
+    }
+
+    pub async fn detect_failures(&self) {
+        let silent = {
+            let mut lock = self.runtime.lock().await;
+            let runtime = &mut *lock;
+            let this_id = runtime.cluster.group.scheduler_info.id;
+
+            runtime
+                .health
+                .silent(&runtime.cluster.servers, Instant::now())
+                .into_iter()
+                .filter(|s| s.id != this_id)
+                .collect::<Vec<_>>()
+        };
+
+        for server in silent {
+            if self.ping(&server).await.is_ok() {
+                self.runtime
+                    .lock()
+                    .await
+                    .health
+                    .heard(server.id, Instant::now());
+                continue;
+            }
+
+            let liveness = self
+                .runtime
+                .lock()
+                .await
+                .health
+                .suspect(&server.id, Instant::now());
+
+            match liveness {
+                Liveness::Suspect => warn!(server_id = %server.id, "server is suspected"),
+                Liveness::Dead => {
+                    if let Err(e) = self.handle_failed_server(&server).await {
+                        error!(server_id = %server.id, "handle_failed_server failed: {e}")
+                    }
+                }
+                Liveness::Alive => {}
+            }
+        }
+    }
+
+    pub async fn ping(&self, server: &ServerInfo) -> Result<()> {
+        let ping = async {
+            let mut client = self.client(server).await?;
+            let response = client.ping(telemetry::request(())).await?;
+            if !response.get_ref().success {
+                return Err("Unsuccessful ping".into());
+            }
+            Ok(())
+        };
+
+        tokio::time::timeout(PING_TIMEOUT, ping)
+            .await
+            .map_err(|_| format!("ping to {:?} timed out", server.id))?
+    }
+
+    pub async fn handle_failed_server(&self, server: &ServerInfo) -> Result<()> {
+        warn!(server_id = %server.id, "server has failed. Removing it.");
+        self.remove_member(server).await
+    }
+
+    /// remove_member removes the server from the cluster,
+    /// and spawns the instances it had on other servers.
+    pub async fn remove_member(&self, server: &ServerInfo) -> Result<()> {
+        {
+            let mut runtime = self.runtime.lock().await;
+
+            if !runtime.cluster.servers.iter().any(|s| s.id == server.id) {
+                warn!(server_id = %server.id, "failed to remove the server from list");
+            }
+            runtime.apply(StateOp::Leave(server.id)).await?;
+            runtime.health.remove(&server.id);
+        }
+
+        self.replace_lost_instances().await;
+
+        Ok(())
+    }
+
+    /// replace_lost_instances spawns instances of the deployments that have fewer instances
+    /// than desired, e.g., because a server hosting them has been removed.
+    /// Re-placing counts as a scale-out, so that it does not race with one from lookups,
+    /// while it is not delayed by the cooldown of a preceding one.
+    pub async fn replace_lost_instances(&self) {
+        let lacking = {
+            let mut runtime = self.runtime.lock().await;
+            let now = Instant::now();
+
+            runtime
+                .cluster
+                .lacking_replicas()
+                .into_iter()
+                .filter_map(|(deployment, count)| {
+                    let began = runtime.scale_out.begin_recovery(deployment.id, now)?;
+                    Some((deployment, count, began))
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (deployment, count, began) in lacking {
+            for _ in 0..count {
+                info!(deployment_id = %deployment.id, "re-placing a lost instance");
+
+                let result = self.deploy_in_us(deployment.clone()).await;
+                if let Err(e) = result {
+                    error!(deployment_id = %deployment.id, "failed to re-place: {e}");
+                    break;
+                }
+            }
+
+            self.runtime
+                .lock()
+                .await
+                .scale_out
+                .end(deployment.id, began, Instant::now());
+        }
+    }
+
+    /// is_alive_as returns true if `server` responds with its id at its address.
+    pub async fn is_alive_as(&self, server: &ServerInfo) -> bool {
+        let get_info = async {
+            let mut client = self.client(server).await?;
+            let response = client
+                .get_info(telemetry::request(GetInfoRequest {}))
+                .await?;
+            let info: ServerInfo = response
+                .into_inner()
+                .server
+                .ok_or("Server cannot be empty".to_owned())?
+                .try_into()?;
+            Ok::<_, Error>(info)
+        };
+
+        match tokio::time::timeout(PING_TIMEOUT, get_info).await {
+            Ok(Ok(info)) => info.id == server.id,
+            _ => false,
+        }
     }
 
     pub async fn client(&self, server: &ServerInfo) -> Result<ServerDaemonClient<Channel>> {
@@ -179,8 +694,6 @@ impl AuthoritativeScheduler {
 #[tonic::async_trait]
 impl Scheduler for AuthoritativeScheduler {
     async fn join(&self, request: Request<JoinRequest>) -> RpcResult<Response<JoinResponse>> {
-        println!("join() called!!");
-
         let proto_server = request
             .get_ref()
             .server
@@ -191,29 +704,95 @@ impl Scheduler for AuthoritativeScheduler {
             .clone()
             .try_into()
             .map_err(<Error as Into<Status>>::into)?;
+        telemetry::record("server_id", server.id);
+
+        // The liveness check releases the lock, so the id is checked again before inserting,
+        // in case another server has joined with it meanwhile
+        let mut dead: Option<ServerInfo> = None;
+        let mut runtime = loop {
+            let runtime = self.runtime.lock().await;
+            let known = runtime
+                .cluster
+                .servers
+                .iter()
+                .find(|s| s.id == server.id && s.addr != server.addr)
+                .cloned();
+
+            let Some(known) = known else {
+                break runtime;
+            };
+
+            if dead.as_ref().is_some_and(|d| d.addr == known.addr) {
+                info!(from = %known.addr, to = %server.addr, "server has moved");
+                break runtime;
+            }
+            drop(runtime);
+
+            // The server may have restarted with another address.
+            // Otherwise, another server is using the same id.
+            if self.is_alive_as(&known).await {
+                return Err(Status::already_exists(format!(
+                    "server id {} is already used by {}",
+                    known.id, known.addr
+                )));
+            }
+            dead = Some(known);
+        };
 
-        self.push_server(server).await;
+        runtime.health.heard(server.id, Instant::now());
+        runtime
+            .apply(StateOp::Join(server))
+            .await
+            .map_err(<Error as Into<Status>>::into)?;
 
-        let group = Some(self.runtime.lock().await.cluster.group.clone().into());
+        let cluster = runtime.cluster.clone();
+        drop(runtime);
 
         Ok(Response::new(JoinResponse {
             success: true,
-            group,
+            group: Some(cluster.group.clone().into()),
+            cluster: Some(cluster.into()),
         }))
     }
 
     async fn report(&self, request: Request<ReportRequest>) -> RpcResult<Response<ReportResponse>> {
-        let ReportRequest { server, windows } = request.into_inner();
+        let ReportRequest {
+            server,
+            windows,
+            latencies,
+            queues,
+        } = request.into_inner();
 
         let server: Server = server.ok_or(Status::aborted("server cannot be empty"))?;
         let server = ServerInfo::try_from(server);
         let server = server.map_err(|e| <Error as Into<Status>>::into(e))?;
-
-        let stats = ServerStats::from_stats(server, windows);
+        telemetry::record("server_id", server.id);
 
         let mut lock = self.runtime.lock().await;
         let runtime = lock.borrow_mut();
 
+        // A server removed as dead must join again
+        if !runtime.cluster.servers.iter().any(|s| s.id == server.id) {
+            return Err(Status::not_found("server is not a member of the cluster"));
+        }
+
+        runtime.health.heard(server.id, Instant::now());
+
+        let latencies = latencies
+            .into_iter()
+            .map(LatencyStats::try_from)
+            .collect::<Result<_>>()
+            .map_err(<Error as Into<Status>>::into)?;
+
+        let queues = queues
+            .into_iter()
+            .map(QueueStats::try_from)
+            .collect::<Result<_>>()
+            .map_err(<Error as Into<Status>>::into)?;
+
+        let mut stats = ServerStats::from_stats(server, windows);
+        stats.update_latencies(latencies);
+        stats.update_queues(queues);
         runtime.cluster.insert_stats(stats);
 
         let cluster = runtime.cluster.clone().into();
@@ -224,27 +803,75 @@ impl Scheduler for AuthoritativeScheduler {
         }))
     }
 
-    async fn deploy(&self, request: Request<DeployRequest>) -> RpcResult<Response<DeployResponse>> {
-        println!("deploy() called!!");
+    async fn leave(&self, request: Request<LeaveRequest>) -> RpcResult<Response<LeaveResponse>> {
+        let LeaveRequest { server } = request.into_inner();
 
-        let DeployRequest { name, source, .. } = request.into_inner();
+        let server: Server = server.ok_or(Status::aborted("server cannot be empty"))?;
+        let server = ServerInfo::try_from(server).map_err(<Error as Into<Status>>::into)?;
+        telemetry::record("server_id", server.id);
+
+        let this_id = self.runtime.lock().await.cluster.group.scheduler_info.id;
+        if server.id == this_id {
+            return Err(Status::failed_precondition(
+                "the scheduler must hand over before leaving",
+            ));
+        }
+
+        info!("server is leaving");
 
-        let deployment_info = DeploymentInfo::new(name, source.clone());
+        // Instances are spawned on other servers before responding,
+        // so that the leaving server can stop once it receives the response.
+        self.remove_member(&server)
+            .await
+            .map_err(<Error as Into<Status>>::into)?;
+
+        Ok(Response::new(LeaveResponse { success: true }))
+    }
+
+    async fn deploy(&self, request: Request<DeployRequest>) -> RpcResult<Response<DeployResponse>> {
+        let DeployRequest {
+            name,
+            source,
+            resources,
+            constraints,
+        } = request.into_inner();
+
+        let resources = resources.map(Resources::from).unwrap_or_default();
+        let constraints = constraints.map(Constraints::from).unwrap_or_default();
+        if !constraints.replicas_allowed(constraints.initial_replicas()) {
+            return Err(Status::invalid_argument(
+                "max_replicas must be at least min_replicas and 1",
+            ));
+        }
+
+        let deployment_info = DeploymentInfo::new(name, source.clone())
+            .with_resources(resources)
+            .with_constraints(constraints);
         let deployment: Deployment = deployment_info.clone().into();
-        println!("created info");
+        telemetry::record("deployment_id", deployment_info.id);
 
-        self.clone_inner()
+        let versions = self
+            .clone_inner()
             .await
             .save_deployment(&deployment_info)
             .await
             .map_err(<Error as Into<Status>>::into)?;
 
+        let entry = CatalogEntryInfo::new(deployment_info.clone(), versions);
+        self.runtime
+            .lock()
+            .await
+            .apply(StateOp::PutDeployment(entry))
+            .await
+            .map_err(<Error as Into<Status>>::into)?;
+
         let mut success = true;
 
-        let resp = self.deploy_in_us(deployment_info).await;
-        let resp = resp.map_err(<Error as Into<Status>>::into)?;
-        success &= resp.success;
-        println!("got resp");
+        for _ in 0..deployment_info.constraints.initial_replicas() {
+            let resp = self.deploy_in_us(deployment_info.clone()).await;
+            let resp = resp.map_err(<Error as Into<Status>>::into)?;
+            success &= resp.success;
+        }
 
         Ok(Response::new(DeployResponse {
             success,
@@ -255,8 +882,18 @@ impl Scheduler for AuthoritativeScheduler {
     async fn lookup(&self, request: Request<LookupRequest>) -> RpcResult<Response<LookupResponse>> {
         let runtime = self.clone_inner().await;
 
-        let id = Uuid::parse_str(&request.get_ref().deployment_id)
-            .map_err(|e| Status::aborted(e.to_string()))?;
+        let LookupRequest { deployment_id, qos } = request.into_inner();
+        let qos: QosInfo = qos.map(QosInfo::from).unwrap_or_default();
+
+        let id = Uuid::parse_str(&deployment_id).map_err(|e| Status::aborted(e.to_string()))?;
+        telemetry::record("deployment_id", id);
+
+        // `runtime` is a clone, so record on the shared one
+        self.runtime
+            .lock()
+            .await
+            .forecast
+            .record(id, Instant::now());
 
         let server_ids = runtime
             .cluster
@@ -264,45 +901,171 @@ impl Scheduler for AuthoritativeScheduler {
             .map_err(|e| Status::aborted(e.to_string()))?;
 
         let stats_map = runtime.cluster.server_stats.clone_by_ids(&server_ids);
+        let hosting: Vec<_> = stats_map.iter().map(|(_, s)| s.server.clone()).collect();
+
+        // Steer away from the instances that are rejecting requests
+        let unsaturated = stats::unsaturated(&id, &stats_map);
+        let saturated = !stats_map.0.is_empty() && unsaturated.0.is_empty();
+        let stats_map = if saturated { stats_map } else { unsaturated };
+
+        let within_target = qos.within_target(&id, &stats_map);
+        let missed_target = !stats_map.0.is_empty() && within_target.0.is_empty();
+
+        let candidates = if !missed_target {
+            within_target
+        } else {
+            warn!("no instance is within the latency target");
+            match qos.accuracy {
+                Accuracy::Low => qos::fastest(&id, &stats_map),
+                _ => stats_map,
+            }
+        };
+
+        let constraints = runtime
+            .cluster
+            .catalog
+            .0
+            .get(&id)
+            .map(|e| e.deployment.constraints.clone())
+            .unwrap_or_default();
+
+        let selected = select_execution(
+            runtime.scheduler.as_ref(),
+            &runtime.execution,
+            &candidates,
+            &constraints,
+            qos.accuracy,
+        );
+
+        let gpu = selected
+            .as_ref()
+            .is_some_and(|(_, mode)| *mode == ExecutionMode::Gpu);
+        let mut decision =
+            DecisionRecord::new(DecisionKind::Lookup, id, &runtime.cluster.policy.name)
+                .with_candidates(&hosting, &runtime.cluster.server_stats)
+                .with_policy_scores(runtime.scheduler.as_ref(), &candidates, gpu);
+        if saturated {
+            decision.note("Every instance was saturated.");
+        }
+        if missed_target {
+            decision.note("No instance was within the latency target.");
+            if qos.accuracy == Accuracy::Low {
+                decision.note("Chose among the fastest instances for low accuracy.");
+            }
+        }
+        if qos.accuracy == Accuracy::High {
+            decision.note("Kept on GPUs even if saturated for high accuracy.");
+        }
+        if let Some((target, mode)) = &selected {
+            decision.chosen = Some(target.clone());
+            decision.mode = *mode;
+        }
+        let decision_id = decision.id;
+        runtime.decisions.record(decision);
 
-        let target = runtime
-            .scheduler
-            .schedule(&stats_map)
-            .ok_or(Status::aborted("Failed to schedule"))?
-            .clone();
+        let (target, mode) = selected.ok_or(Status::aborted("Failed to schedule"))?;
 
         // Clone self.
         // Because we have Arc<Mutex<_>> inside Self, we can edit the inner data from the clone.
         let this = self.clone();
         let target_moved = target.clone();
-        tokio::task::spawn(async move {
-            let runtime = this.runtime.lock().await;
-
-            let should_scale = {
-                let stats = runtime
-                    .cluster
-                    .server_stats
-                    .0
-                    .get(&target_moved.id)
-                    .ok_or(())?;
-                runtime.scheduler.needs_scale_out(&target_moved, stats)
+        tokio::task::spawn(telemetry::in_current_trace(async move {
+            let (deployment, began) = {
+                let mut runtime = this.runtime.lock().await;
+
+                // Add an instance that may meet the target, or take the excess requests
+                let should_scale = missed_target || saturated || {
+                    let stats = runtime
+                        .cluster
+                        .server_stats
+                        .0
+                        .get(&target_moved.id)
+                        .ok_or(())?;
+                    runtime.scheduler.needs_scale_out(&target_moved, stats)
+                };
+
+                if !should_scale {
+                    return Ok(());
+                }
+
+                let entry = runtime.cluster.catalog.0.get(&id).ok_or(())?;
+                let deployment = entry.deployment.clone();
+
+                // Another lookup may have triggered the same scale-out
+                let Some(began) = runtime.begin_scale_out(&deployment, Instant::now()) else {
+                    return Ok(());
+                };
+                runtime
+                    .decisions
+                    .update(&decision_id, |d| d.scale_out = true);
+
+                (deployment, began)
             };
 
-            if should_scale {
-                let deployment = runtime.deployments.0.get(&id).ok_or(())?;
-                this.deploy_in_us(deployment.clone())
-                    .await
-                    .err()
-                    .map(|e| println!("ERR: deploy_in_us failed: {e}"));
-            }
+            this.scale_out(deployment, began).await;
 
             Ok::<(), ()>(())
-        });
+        }));
 
-        Ok(Response::new(LookupResponse {
+        let mut response = LookupResponse {
             success: true,
             deployment_id: id.to_string(),
             server: Some(target.into()),
+            decision_id: decision_id.to_string(),
+            ..Default::default()
+        };
+        response.set_mode(mode);
+
+        Ok(Response::new(response))
+    }
+
+    async fn list_decisions(
+        &self,
+        request: Request<ListDecisionsRequest>,
+    ) -> RpcResult<Response<ListDecisionsResponse>> {
+        let ListDecisionsRequest {
+            deployment_id,
+            limit,
+        } = request.into_inner();
+
+        let deployment_id = deployment_id
+            .map(|id| Uuid::parse_str(&id))
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let limit = if limit == 0 { usize::MAX } else { limit as _ };
+
+        let decisions = self
+            .runtime
+            .lock()
+            .await
+            .decisions
+            .list(deployment_id.as_ref(), limit)
+            .into_iter()
+            .map(|d| d.into())
+            .collect();
+
+        Ok(Response::new(ListDecisionsResponse { decisions }))
+    }
+
+    async fn explain(
+        &self,
+        request: Request<ExplainRequest>,
+    ) -> RpcResult<Response<ExplainResponse>> {
+        let ExplainRequest { decision_id } = request.into_inner();
+
+        let id =
+            Uuid::parse_str(&decision_id).map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let decision = self
+            .runtime
+            .lock()
+            .await
+            .decisions
+            .get(&id)
+            .ok_or_else(|| Status::not_found(format!("Decision {id} is not in the log")))?;
+
+        Ok(Response::new(ExplainResponse {
+            decision: Some(decision.into()),
         }))
     }
 }
@@ -330,18 +1093,232 @@ impl SchedulerRuntime {
         Self {
             cluster,
             scheduler,
-            deployments: IdMap::new(),
             database,
+            scale_in: ScaleInConfig::default(),
+            execution: ExecutionConfig::default(),
+            underutilized_since: IdMap::new(),
+            health: FailureDetector::new(FailureDetectorConfig::default()),
+            placement: BestFitPlacement::new(),
+            forecast: RateForecaster::new(ForecastConfig::default()),
+            scale_out: ScaleOutTracker::new(ScaleOutConfig::default()),
+            decisions: DecisionLog::default(),
+            metrics: Metrics::new(),
+            store: None,
+        }
+    }
+
+    /// place_instance chooses the server to spawn an instance of the deployment.
+    pub fn place_instance(&self, deployment: &DeploymentInfo) -> Result<ServerInfo> {
+        self.cluster
+            .place_instance(self.scheduler.as_ref(), &self.placement, deployment)
+    }
+
+    /// record_placement records the outcome of `place_instance` in the decision log.
+    pub fn record_placement(&self, deployment: &DeploymentInfo, placed: &Result<ServerInfo>) {
+        let policy = if deployment.resources.is_empty() {
+            &self.cluster.policy.name
+        } else {
+            "best-fit"
+        };
+
+        let accepted: Vec<_> = self
+            .cluster
+            .servers
+            .iter()
+            .filter(|s| self.scheduler.accepts(s, &deployment.constraints))
+            .cloned()
+            .collect();
+
+        let stats_map = &self.cluster.server_stats;
+        let decision = DecisionRecord::new(DecisionKind::Placement, deployment.id, policy)
+            .with_candidates(&accepted, stats_map);
+        let mut decision = if deployment.resources.is_empty() {
+            let gpu = deployment.constraints.gpu_required;
+            decision.with_policy_scores(self.scheduler.as_ref(), stats_map, gpu)
+        } else {
+            // Best-fit ranks on the dominant share after placement
+            decision.with_scores(|server| {
+                ServerCapacity::of(&self.cluster, server).dominant_share(&deployment.resources)
+            })
+        };
+
+        let excluded = self.cluster.servers.len() - accepted.len();
+        if excluded > 0 {
+            decision.note(format!(
+                "{excluded} server(s) excluded by the constraints of the deployment."
+            ));
+        }
+
+        match placed {
+            Ok(server) => decision.chosen = Some(server.clone()),
+            Err(e) => decision.note(format!("Placement failed: {e}")),
+        }
+
+        self.decisions.record(decision);
+    }
+
+    /// schedule picks a server with the GPU policy if `gpu_required`, with the CPU one otherwise.
+    pub fn schedule(&self, stats_map: &StatsMap, gpu_required: bool) -> Option<ServerInfo> {
+        if gpu_required {
+            self.scheduler.schedule_gpu(stats_map)
+        } else {
+            self.scheduler.schedule(stats_map)
+        }
+    }
+
+    /// apply persists `op` before applying it to the cluster.
+    /// Files are written on the blocking pool, as each entry is synced to the disk.
+    pub async fn apply(&mut self, op: StateOp) -> Result<()> {
+        if let Some(store) = self.store.clone() {
+            let logged = op.clone();
+            spawn_blocking(move || store.append(&logged))
+                .await
+                .map_err(|e| format!("failed to append to the state log: {e}"))??;
+        }
+
+        self.cluster.apply(op);
+
+        if let Some(store) = self.store.clone().filter(|s| s.needs_compaction()) {
+            let cluster = self.cluster.clone();
+            spawn_blocking(move || store.snapshot(&cluster))
+                .await
+                .map_err(|e| format!("failed to snapshot the state: {e}"))??;
         }
+
+        Ok(())
     }
 
-    pub async fn save_deployment(&mut self, deployment: &DeploymentInfo) -> Result<()> {
+    pub async fn save_deployment(
+        &mut self,
+        deployment: &DeploymentInfo,
+    ) -> Result<Vec<SavedDeployment>> {
         self.database
             .add_app(deployment)
             .await
             .map_err(|e| format!("Failed to save deployment: {e}"))?;
 
-        Ok(())
+        Ok(self.database.versions(&deployment.id).await)
+    }
+
+    /// predicted_scale_out_targets returns the deployments whose instances are forecasted to
+    /// exceed the scale-out threshold within the horizon, assuming that the CPU utilization
+    /// grows in proportion to the request rate. The scale-outs of them have begun at the time
+    /// returned with each.
+    pub fn predicted_scale_out_targets(&mut self, now: Instant) -> Vec<(DeploymentInfo, Instant)> {
+        self.forecast.tick(now);
+
+        let threshold = self
+            .cluster
+            .policy
+            .scale_out_threshold
+            .unwrap_or(SCALEOUT_THREASHOLD) as f64;
+
+        let mut targets = vec![];
+
+        for (id, instances) in self.cluster.instances.iter() {
+            if self.forecast.in_cooldown(id, now) {
+                continue;
+            }
+
+            let Some(forecast) = self.forecast.forecast(id) else {
+                continue;
+            };
+            if forecast.current <= 0. || forecast.predicted <= forecast.current {
+                continue;
+            }
+
+            let server_ids: Vec<_> = instances.servers.iter().map(|s| s.id).collect();
+            let stats_map = self.cluster.server_stats.clone_by_ids(&server_ids);
+            let averages: Vec<_> = stats_map
+                .iter()
+                .filter_map(|(_, s)| s.average().map(|u| u.cpu))
+                .collect();
+            if averages.is_empty() {
+                continue;
+            }
+
+            let cpu = averages.iter().sum::<f64>() / averages.len() as f64;
+            let predicted_cpu = cpu * forecast.predicted / forecast.current;
+            if predicted_cpu <= threshold {
+                continue;
+            }
+
+            targets.push(instances.deployment.clone());
+        }
+
+        let began: Vec<_> = targets
+            .into_iter()
+            .filter_map(|d| self.begin_scale_out(&d, now).map(|began| (d, began)))
+            .collect();
+        for (deployment, _) in &began {
+            self.forecast.scaled_out(deployment.id, now);
+        }
+
+        began
+    }
+
+    /// begin_scale_out marks a scale-out of the deployment in flight, and returns when it began.
+    /// Returns `None` if the deployment has the maximum number of instances, or another
+    /// scale-out of it is in flight or has just finished.
+    pub fn begin_scale_out(
+        &mut self,
+        deployment: &DeploymentInfo,
+        now: Instant,
+    ) -> Option<Instant> {
+        let count = self.cluster.instance_count(&deployment.id);
+        if !deployment.constraints.replicas_allowed(count + 1) || !self.scale_out.allows(count) {
+            return None;
+        }
+
+        let began = self.scale_out.begin(deployment.id, now)?;
+        self.metrics.observe_scale_out(deployment.id);
+
+        Some(began)
+    }
+
+    /// scale_in_targets returns a replica to destroy for each deployment that has stayed
+    /// underutilized for the configured window.
+    pub fn scale_in_targets(&mut self, now: Instant) -> Vec<(Uuid, ServerInfo)> {
+        let mut targets = vec![];
+
+        for (id, instances) in self.cluster.instances.iter() {
+            let min_replicas = (instances.deployment.constraints.min_replicas as usize)
+                .max(self.scale_in.min_replicas);
+            // Do not undo a scale-out ahead of the load
+            if instances.servers.len() <= min_replicas || self.forecast.in_cooldown(id, now) {
+                self.underutilized_since.0.remove(id);
+                continue;
+            }
+
+            let server_ids: Vec<_> = instances.servers.iter().map(|s| s.id).collect();
+            let stats_map = self.cluster.server_stats.clone_by_ids(&server_ids);
+
+            let underutilized = stats_map.0.len() == server_ids.len()
+                && stats_map
+                    .iter()
+                    .all(|(_, stats)| self.scheduler.needs_scale_in(&stats.server, stats));
+
+            if !underutilized {
+                self.underutilized_since.0.remove(id);
+                continue;
+            }
+
+            let since = *self.underutilized_since.0.entry(*id).or_insert(now);
+            if now.duration_since(since) < self.scale_in.window {
+                continue;
+            }
+
+            if let Some(target) = stats::least_utilized(&stats_map) {
+                targets.push((*id, target));
+            }
+        }
+
+        // Wait for another window before scaling in the same deployment again
+        for (id, _) in &targets {
+            self.underutilized_since.0.remove(id);
+        }
+
+        targets
     }
 
     pub fn wrap(self) -> Arc<Mutex<Self>> {
@@ -349,23 +1326,39 @@ impl SchedulerRuntime {
     }
 
     pub async fn clone_inner(arc: &Arc<Mutex<Self>>) -> Self {
-        println!("WARN: cloning SchedulerRuntime");
+        debug!("cloning SchedulerRuntime");
         arc.lock().await.clone()
     }
 }
 
+const PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+impl Default for ScaleInConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            window: Duration::from_secs(60),
+            min_replicas: 1,
+        }
+    }
+}
+
 impl Cluster {
     pub fn new(scheduler: &ServerInfo) -> Self {
         let group = GroupInfo::new(scheduler);
         let servers = vec![scheduler.clone()];
         let instances = AppInstanceMap::new();
+        let catalog = Catalog::new();
         let server_stats = StatsMap::new();
+        let policy = PolicyInfo::default();
 
         Self {
             group,
             servers,
             instances,
+            catalog,
             server_stats,
+            policy,
         }
     }
 
@@ -373,46 +1366,122 @@ impl Cluster {
         let group = group.clone();
         let servers = vec![group.scheduler_info.clone()];
         let instances = AppInstanceMap::new();
+        let catalog = Catalog::new();
         let server_stats = StatsMap::new();
+        let policy = PolicyInfo::default();
 
         Self {
             group,
             servers,
             instances,
+            catalog,
             server_stats,
+            policy,
         }
     }
 
-    /// choose_scheduler chooses a scheduler based on their ids.
-    /// A server with the lowest id is chosen.
-    ///
-    /// NOTE:
-    /// This function assumes there is at least one server in the `.servers`
-    /// becouse the calling server instance must be included.
-    pub fn choose_scheduler(&mut self) -> &ServerInfo {
-        let mut lowest_id_server = &self.servers[0];
-
-        for server in &self.servers {
-            if server.id < lowest_id_server.id {
-                lowest_id_server = server;
-            }
-        }
-
-        self.group.scheduler_info = lowest_id_server.clone();
-        return &lowest_id_server;
-    }
-
     pub fn next_cluster(&self, scheduler_info: &ServerInfo) -> Self {
         let number = self.group.number + 1;
         let other_group = GroupInfo::with_number(scheduler_info, number);
         Self::with_group(&other_group)
     }
 
+    /// handed_over returns the cluster of the next group, whose scheduler is `scheduler_info`.
+    /// Unlike `next_cluster`, the members and instances are kept.
+    pub fn handed_over(&self, scheduler_info: &ServerInfo) -> Self {
+        let number = self.group.number + 1;
+        let group = GroupInfo::with_number(scheduler_info, number);
+
+        Self {
+            group,
+            ..self.clone()
+        }
+    }
+
     pub fn to_nomination(&self) -> Nomination {
         let cluster = Some(self.clone().into());
         Nomination { cluster }
     }
 
+    /// restored_by returns the cluster restored from disk, whose scheduler is `this_server`.
+    /// The group number is advanced so that members can tell the restarted scheduler apart.
+    pub fn restored_by(mut self, this_server: &ServerInfo) -> Self {
+        let previous = self.group.scheduler_info.id;
+
+        // Instances on this server did not survive the restart.
+        // They are spawned again once the failure detector finds them lacking.
+        for id in [previous, this_server.id] {
+            let deployment_ids: Vec<_> = self.deployments_on(&id).iter().map(|d| d.id).collect();
+            for deployment_id in deployment_ids {
+                self.remove_instance(&deployment_id, &id);
+            }
+        }
+
+        if previous != this_server.id {
+            self.remove_server(&previous);
+        }
+        self.apply(StateOp::Join(this_server.clone()));
+
+        self.group = GroupInfo::with_number(this_server, self.group.number + 1);
+
+        self
+    }
+
+    pub fn apply(&mut self, op: StateOp) {
+        use StateOp::*;
+
+        match op {
+            Join(server) => self.upsert_server(server),
+            Leave(id) => {
+                self.remove_server(&id);
+            }
+            PutDeployment(entry) => {
+                self.catalog.0.insert(entry.deployment.id, entry);
+            }
+            RemoveDeployment(id) => {
+                self.catalog.0.remove(&id);
+            }
+            AddInstance(deployment, server) => self.insert_instance(deployment, vec![server]),
+            RemoveInstance(deployment_id, server_id) => {
+                self.remove_instance(&deployment_id, &server_id);
+            }
+        }
+    }
+
+    /// upsert_server adds the server to the members.
+    /// If it is already a member, its address is updated everywhere it appears.
+    pub fn upsert_server(&mut self, server: ServerInfo) {
+        let Some(known) = self.servers.iter_mut().find(|s| s.id == server.id) else {
+            self.servers.push(server);
+            return;
+        };
+        *known = server.clone();
+
+        let locations = self
+            .instances
+            .0
+            .values_mut()
+            .flat_map(|i| i.servers.iter_mut());
+        for location in locations.filter(|s| s.id == server.id) {
+            *location = server.clone();
+        }
+
+        if let Some(stats) = self.server_stats.0.get_mut(&server.id) {
+            stats.server = server;
+        }
+    }
+
+    pub fn insert_deployment(
+        &mut self,
+        deployment: DeploymentInfo,
+        versions: Vec<SavedDeployment>,
+    ) {
+        let id = deployment.id;
+        self.catalog
+            .0
+            .insert(id, CatalogEntryInfo::new(deployment, versions));
+    }
+
     pub fn insert_instance(&mut self, deployment: DeploymentInfo, servers: Vec<ServerInfo>) {
         let id = deployment.id;
         self.instances
@@ -423,25 +1492,179 @@ impl Cluster {
                 deployment,
                 servers,
             });
+
+        // Spawning more instances raises the desired count, while losing them does not
+        let count = self.instance_count(&id);
+        if let Some(entry) = self.catalog.0.get_mut(&id) {
+            entry.desired_replicas = entry.desired_replicas.max(count);
+        }
+    }
+
+    pub fn instance_count(&self, deployment_id: &Uuid) -> usize {
+        self.instances
+            .0
+            .get(deployment_id)
+            .map_or(0, |i| i.servers.len())
+    }
+
+    /// lacking_replicas returns the deployments with fewer instances than desired,
+    /// with the number of missing instances.
+    pub fn lacking_replicas(&self) -> Vec<(DeploymentInfo, usize)> {
+        self.catalog
+            .iter()
+            .filter_map(|(id, entry)| {
+                let missing = entry
+                    .desired_replicas
+                    .saturating_sub(self.instance_count(id));
+                (missing > 0).then(|| (entry.deployment.clone(), missing))
+            })
+            .collect()
+    }
+
+    /// place_instance chooses the server to spawn an instance of the deployment.
+    /// Servers without an instance of it yet are preferred.
+    pub fn place_instance(
+        &self,
+        scheduler: &dyn DeploymentScheduler,
+        placement: &BestFitPlacement,
+        deployment: &DeploymentInfo,
+    ) -> Result<ServerInfo> {
+        if deployment.resources.is_empty() {
+            let accepts = |s: &ServerInfo| scheduler.accepts(s, &deployment.constraints);
+            let candidates = self.placement_candidates(&deployment.id, accepts);
+
+            let target = if deployment.constraints.gpu_required {
+                scheduler.schedule_gpu(&candidates)
+            } else {
+                scheduler.schedule(&candidates)
+            };
+            if let Some(target) = target {
+                return Ok(target);
+            }
+
+            // Servers that have not reported yet are not candidates of the policy
+            let fallback = self.servers.iter().find(|s| accepts(s)).ok_or(format!(
+                "No server satisfies the constraints of {:?}",
+                deployment.id
+            ))?;
+            warn!(server_id = %fallback.id, "failed to schedule. Using the first accepted server");
+
+            return Ok(fallback.clone());
+        }
+
+        let (hosting, others): (Vec<_>, Vec<_>) = self
+            .servers
+            .iter()
+            .filter(|s| scheduler.accepts(s, &deployment.constraints))
+            .map(|s| ServerCapacity::of(self, s))
+            .partition(|c| {
+                self.deployments_on(&c.server.id)
+                    .iter()
+                    .any(|d| d.id == deployment.id)
+            });
+
+        let resources = &deployment.resources;
+        let target = placement
+            .place(&others, resources)
+            .or_else(|| placement.place(&hosting, resources))
+            .ok_or(format!(
+                "No server has enough resources for {:?}",
+                deployment.id
+            ))?;
+
+        Ok(target)
+    }
+
+    /// placement_candidates returns the stats of accepted servers that do not have an instance
+    /// of the deployment yet. All accepted servers are candidates if every one of them has one.
+    pub fn placement_candidates<F>(&self, deployment_id: &Uuid, accepts: F) -> StatsMap
+    where
+        F: Fn(&ServerInfo) -> bool,
+    {
+        let hosting: Vec<_> = self
+            .instances
+            .0
+            .get(deployment_id)
+            .map(|i| i.servers.iter().map(|s| s.id).collect())
+            .unwrap_or_default();
+
+        let accepted: Vec<_> = self
+            .servers
+            .iter()
+            .filter(|s| accepts(s))
+            .map(|s| s.id)
+            .collect();
+
+        let ids: Vec<_> = accepted
+            .iter()
+            .filter(|id| !hosting.contains(id))
+            .cloned()
+            .collect();
+
+        let candidates = self.server_stats.clone_by_ids(&ids);
+        if candidates.0.is_empty() {
+            self.server_stats.clone_by_ids(&accepted)
+        } else {
+            candidates
+        }
+    }
+
+    /// remove_instance removes `server_id` from the locations of the deployment.
+    /// The deployment itself is removed when it has no more instances.
+    pub fn remove_instance(
+        &mut self,
+        deployment_id: &Uuid,
+        server_id: &Uuid,
+    ) -> Option<ServerInfo> {
+        let instances = self.instances.0.get_mut(deployment_id)?;
+
+        let index = instances.servers.iter().position(|s| &s.id == server_id)?;
+        let removed = instances.servers.remove(index);
+
+        if instances.servers.is_empty() {
+            self.instances.0.remove(deployment_id);
+        }
+
+        Some(removed)
     }
 
     pub fn insert_stats(&mut self, stats: ServerStats) {
-        let id = stats.server.id;
-        self.server_stats
+        let config = self.policy.stats_config();
+
+        let entry = self
+            .server_stats
             .0
-            .entry(id)
-            .and_modify(|s| s.append(stats.stats.clone()))
-            .or_insert(stats);
+            .entry(stats.server.id)
+            .or_insert_with(|| ServerStats::with_config(stats.server.clone(), config));
+
+        // The policy may have been replaced since the stats were created
+        entry.configure(config);
+        entry.merge(stats);
     }
 
+    /// remove_server removes the server from the members, and its instances and stats.
     pub fn remove_server(&mut self, id: &Uuid) -> Option<ServerInfo> {
+        let deployment_ids: Vec<_> = self.deployments_on(id).iter().map(|d| d.id).collect();
+        for deployment_id in deployment_ids {
+            self.remove_instance(&deployment_id, id);
+        }
+
+        self.server_stats.0.remove(id);
+
         let index = self.servers.iter().position(|s| &s.id == id)?;
         Some(self.servers.remove(index))
     }
 
-    pub fn get_instance_server_ids(&self, deployment_id: &Uuid) -> Result<Vec<Uuid>> {
-        println!("instances = {:?}", self.instances.0);
+    /// deployments_on returns the deployments that have an instance on the server.
+    pub fn deployments_on(&self, server_id: &Uuid) -> Vec<DeploymentInfo> {
+        self.instances
+            .iter()
+            .filter(|(_, i)| i.servers.iter().any(|s| &s.id == server_id))
+            .map(|(_, i)| i.deployment.clone())
+            .collect()
+    }
 
+    pub fn get_instance_server_ids(&self, deployment_id: &Uuid) -> Result<Vec<Uuid>> {
         Ok(self
             .instances
             .0
@@ -468,11 +1691,15 @@ impl Into<ClusterState> for Cluster {
             .values()
             .map(|i| i.clone().into())
             .collect();
+        let catalog = self.catalog.0.values().map(|e| e.clone().into()).collect();
+        let policy = Some(self.policy.into());
 
         ClusterState {
             group,
             servers,
             instances,
+            catalog,
+            policy,
         }
     }
 }
@@ -498,13 +1725,54 @@ impl TryFrom<ClusterState> for Cluster {
             .collect::<Result<HashMap<_, _, _>>>()
             .map(IdMap)?;
 
+        let catalog = state
+            .catalog
+            .into_iter()
+            .map(|e| CatalogEntryInfo::try_from(e).map(|ee| (ee.deployment.id, ee)))
+            .collect::<Result<HashMap<_, _, _>>>()
+            .map(IdMap)?;
+
         let server_stats = IdMap::new();
 
+        let policy = match state.policy {
+            Some(policy) => policy.try_into()?,
+            None => PolicyInfo::default(),
+        };
+
         Ok(Self {
             group,
             servers,
             instances,
+            catalog,
             server_stats,
+            policy,
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::mean::MeanScheduler;
+    use super::*;
+
+    #[test]
+    fn test_place_requires_accepted_server() {
+        let scheduler = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let mut cluster = Cluster::new(&scheduler);
+        cluster.apply(StateOp::Join(ServerInfo::with_id(
+            "127.0.0.1:50052",
+            Uuid::new_v4(),
+        )));
+
+        // No server has reported stats, nor has a GPU
+        let mut deployment = DeploymentInfo::new("test".to_owned(), "".to_owned());
+        deployment.constraints.gpu_required = true;
+
+        let placed = cluster.place_instance(
+            &MeanScheduler::default(),
+            &BestFitPlacement::new(),
+            &deployment,
+        );
+        assert!(placed.is_err());
+    }
+}
@@ -0,0 +1,328 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+
+use uuid::Uuid;
+
+use crate::proto::{Decision, DecisionCandidate, DecisionKind, ExecutionMode};
+use crate::ServerInfo;
+
+use super::interface::DeploymentScheduler;
+use super::stats::StatsMap;
+
+/// DecisionLog keeps the latest scheduling decisions, so that they can be explained later.
+/// Clones share the same log, as the runtime is cloned to handle each lookup.
+#[derive(Clone, Debug)]
+pub struct DecisionLog {
+    inner: Arc<Mutex<LogState>>,
+}
+
+#[derive(Debug)]
+struct LogState {
+    capacity: usize,
+    /// From the oldest
+    decisions: VecDeque<DecisionRecord>,
+}
+
+/// DecisionRecord is what the scheduler knew, and what it chose.
+#[derive(Clone, Debug)]
+pub struct DecisionRecord {
+    pub id: Uuid,
+    pub timestamp: SystemTime,
+    pub kind: DecisionKind,
+    pub deployment_id: Uuid,
+    pub policy: String,
+    pub candidates: Vec<CandidateRecord>,
+    /// `None` if no server could be chosen
+    pub chosen: Option<ServerInfo>,
+    /// Only meaningful for lookups
+    pub mode: ExecutionMode,
+    pub scale_out: bool,
+    /// Why the candidates were narrowed down, or why the decision failed
+    pub notes: Vec<String>,
+}
+
+/// CandidateRecord is the state of a candidate server at the time of the decision.
+#[derive(Clone, Debug)]
+pub struct CandidateRecord {
+    pub server: ServerInfo,
+    /// Averaged utilization in percent. `None` if the server has not reported.
+    pub cpu: Option<f64>,
+    pub gpu: Option<f64>,
+    pub p95_micros: Option<u64>,
+    pub saturated: bool,
+    /// What the policy ranked the candidate on. `None` if it does not rank candidates.
+    pub score: Option<f64>,
+}
+
+pub const DEFAULT_CAPACITY: usize = 256;
+
+impl DecisionLog {
+    pub fn new(capacity: usize) -> Self {
+        let state = LogState {
+            capacity,
+            decisions: VecDeque::new(),
+        };
+        Self {
+            inner: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// record appends the decision, dropping the oldest one if the log is full.
+    pub fn record(&self, decision: DecisionRecord) {
+        let mut state = self.lock();
+        state.decisions.push_back(decision);
+        while state.decisions.len() > state.capacity {
+            state.decisions.pop_front();
+        }
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<DecisionRecord> {
+        self.lock().decisions.iter().find(|d| &d.id == id).cloned()
+    }
+
+    /// update modifies the decision in place. Returns `false` if it has been dropped.
+    pub fn update<F>(&self, id: &Uuid, f: F) -> bool
+    where
+        F: FnOnce(&mut DecisionRecord),
+    {
+        let mut state = self.lock();
+        let decision = state.decisions.iter_mut().find(|d| &d.id == id);
+        decision.map(f).is_some()
+    }
+
+    /// list returns up to `limit` latest decisions, from the newest.
+    /// Only those of `deployment_id` are returned if it is given.
+    pub fn list(&self, deployment_id: Option<&Uuid>, limit: usize) -> Vec<DecisionRecord> {
+        self.lock()
+            .decisions
+            .iter()
+            .rev()
+            .filter(|d| deployment_id.map_or(true, |id| &d.deployment_id == id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LogState> {
+        self.inner.lock().expect("decision log lock is poisoned")
+    }
+}
+
+impl DecisionRecord {
+    pub fn new(kind: DecisionKind, deployment_id: Uuid, policy: &str) -> Self {
+        Self {
+            id: Uuid::new_v4(),
+            timestamp: SystemTime::now(),
+            kind,
+            deployment_id,
+            policy: policy.to_owned(),
+            candidates: vec![],
+            chosen: None,
+            mode: ExecutionMode::Unspecified,
+            scale_out: false,
+            notes: vec![],
+        }
+    }
+
+    /// with_candidates records the state of `servers`, as found in `stats_map`.
+    pub fn with_candidates(mut self, servers: &[ServerInfo], stats_map: &StatsMap) -> Self {
+        self.candidates = servers
+            .iter()
+            .map(|server| {
+                let stats = stats_map.0.get(&server.id);
+                let average = stats.and_then(|s| s.average());
+
+                CandidateRecord {
+                    server: server.clone(),
+                    cpu: average.map(|u| u.cpu),
+                    gpu: average.map(|u| u.gpu),
+                    p95_micros: stats
+                        .and_then(|s| s.p95_latency(&self.deployment_id))
+                        .map(|p| p.as_micros() as _),
+                    saturated: stats.is_some_and(|s| s.is_saturated(&self.deployment_id)),
+                    score: None,
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// with_scores records the value that each candidate was ranked on.
+    pub fn with_scores<F>(mut self, score: F) -> Self
+    where
+        F: Fn(&ServerInfo) -> Option<f64>,
+    {
+        for candidate in &mut self.candidates {
+            candidate.score = score(&candidate.server);
+        }
+        self
+    }
+
+    /// with_policy_scores records the scores of `scheduler` from the stats in `stats_map`.
+    pub fn with_policy_scores(
+        self,
+        scheduler: &dyn DeploymentScheduler,
+        stats_map: &StatsMap,
+        gpu: bool,
+    ) -> Self {
+        self.with_scores(|server| {
+            let stats = stats_map.0.get(&server.id)?;
+            scheduler.score(stats, gpu)
+        })
+    }
+
+    pub fn note(&mut self, note: impl Into<String>) {
+        self.notes.push(note.into());
+    }
+
+    /// explain describes the decision in a sentence or a few.
+    pub fn explain(&self) -> String {
+        let kind = match self.kind {
+            DecisionKind::Unspecified => "Decision",
+            DecisionKind::Lookup => "Lookup",
+            DecisionKind::Placement => "Placement",
+        };
+
+        let mut lines = vec![format!(
+            "{kind} of deployment {} by the '{}' policy among {} candidate(s).",
+            self.deployment_id,
+            self.policy,
+            self.candidates.len()
+        )];
+
+        for c in &self.candidates {
+            let percent = |v: Option<f64>| v.map_or("-".to_owned(), |v| format!("{v:.1}%"));
+            let p95 = c
+                .p95_micros
+                .map_or("-".to_owned(), |p| format!("{}ms", p / 1000));
+            let saturated = if c.saturated { ", saturated" } else { "" };
+            let score = c.score.map_or(String::new(), |s| format!(", score {s:.1}"));
+
+            lines.push(format!(
+                "  {} ({}): cpu {}, gpu {}, p95 {p95}{saturated}{score}",
+                c.server.id,
+                c.server.addr,
+                percent(c.cpu),
+                percent(c.gpu)
+            ));
+        }
+
+        lines.extend(self.notes.iter().cloned());
+
+        match (&self.chosen, self.kind) {
+            (Some(server), DecisionKind::Lookup) => lines.push(format!(
+                "Chose {} ({}) to run on {}.",
+                server.id,
+                server.addr,
+                self.mode.as_str_name()
+            )),
+            (Some(server), _) => lines.push(format!("Chose {} ({}).", server.id, server.addr)),
+            (None, _) => lines.push("No server was chosen.".to_owned()),
+        }
+
+        if self.scale_out {
+            lines.push("Triggered a scale-out.".to_owned());
+        }
+
+        lines.join("\n")
+    }
+}
+
+impl Default for DecisionLog {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl Into<Decision> for DecisionRecord {
+    fn into(self) -> Decision {
+        let explanation = self.explain();
+
+        let candidates = self
+            .candidates
+            .into_iter()
+            .map(|c| DecisionCandidate {
+                server: Some(c.server.into()),
+                cpu: c.cpu,
+                gpu: c.gpu,
+                p95_micros: c.p95_micros,
+                saturated: c.saturated,
+                score: c.score,
+            })
+            .collect();
+
+        let mut decision = Decision {
+            id: self.id.to_string(),
+            timestamp: Some(self.timestamp.into()),
+            deployment_id: self.deployment_id.to_string(),
+            policy: self.policy,
+            candidates,
+            chosen: self.chosen.map(|s| s.into()),
+            scale_out: self.scale_out,
+            notes: self.notes,
+            explanation,
+            ..Default::default()
+        };
+        decision.set_kind(self.kind);
+        decision.set_mode(self.mode);
+
+        decision
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(deployment_id: Uuid) -> DecisionRecord {
+        DecisionRecord::new(DecisionKind::Lookup, deployment_id, "mean")
+    }
+
+    #[test]
+    fn test_bounded_and_filtered() {
+        let log = DecisionLog::new(3);
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+
+        let first = record(a);
+        let first_id = first.id;
+        log.record(first);
+        for id in [a, b, a] {
+            log.record(record(id));
+        }
+
+        // The first one is dropped
+        assert!(log.get(&first_id).is_none());
+        assert_eq!(log.list(None, 10).len(), 3);
+        assert_eq!(log.list(Some(&a), 10).len(), 2);
+        assert_eq!(log.list(None, 1)[0].deployment_id, a);
+    }
+
+    #[test]
+    fn test_clones_share_log() {
+        let log = DecisionLog::default();
+        let decision = record(Uuid::new_v4());
+        let id = decision.id;
+
+        log.clone().record(decision);
+        assert!(log.clone().update(&id, |d| d.scale_out = true));
+
+        assert!(log.get(&id).unwrap().scale_out);
+    }
+
+    #[test]
+    fn test_explain() {
+        let server = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let mut decision = record(Uuid::new_v4())
+            .with_candidates(&[server.clone()], &StatsMap::new())
+            .with_scores(|_| Some(42.));
+        decision.chosen = Some(server.clone());
+        decision.scale_out = true;
+
+        let explanation = decision.explain();
+        assert!(explanation.contains(&server.id.to_string()));
+        assert!(explanation.contains("score 42.0"));
+        assert!(explanation.contains("scale-out"));
+    }
+}
@@ -0,0 +1,175 @@
+use laqista_core::Constraints;
+
+use crate::proto::{Accuracy, ExecutionMode};
+use crate::utils::IdMap;
+use crate::ServerInfo;
+
+use super::interface::DeploymentScheduler;
+use super::stats::{ServerStats, StatsMap};
+
+/// ExecutionConfig decides when requests are steered from GPUs to CPUs.
+#[derive(Clone, Debug)]
+pub struct ExecutionConfig {
+    /// GPU utilization in percent at or above which a GPU is regarded as saturated
+    pub gpu_saturation: f64,
+}
+
+/// select_execution picks the server to send a request to, and how it should be run there.
+/// Requests run on GPUs while any candidate has a GPU with room, and fall back to CPU/WASM
+/// execution once every GPU is saturated, unless the deployment requires a GPU.
+/// Requests for high accuracy stay on saturated GPUs too, and only run on CPUs without any GPU.
+pub fn select_execution(
+    scheduler: &dyn DeploymentScheduler,
+    config: &ExecutionConfig,
+    candidates: &StatsMap,
+    constraints: &Constraints,
+    accuracy: Accuracy,
+) -> Option<(ServerInfo, ExecutionMode)> {
+    if constraints.gpu_required {
+        let server = scheduler.schedule_gpu(candidates)?;
+        return Some((server, ExecutionMode::Gpu));
+    }
+
+    let with_gpu = |saturated_too: bool| -> StatsMap {
+        IdMap(
+            candidates
+                .iter()
+                .filter(|(_, stats)| {
+                    has_gpu(&stats.server) && (saturated_too || !config.is_saturated(stats))
+                })
+                .map(|(id, stats)| (*id, stats.clone()))
+                .collect(),
+        )
+    };
+
+    let available = with_gpu(accuracy == Accuracy::High);
+    if !available.0.is_empty() {
+        let server = scheduler.schedule_gpu(&available)?;
+        return Some((server, ExecutionMode::Gpu));
+    }
+
+    let server = scheduler.schedule(candidates)?;
+    Some((server, ExecutionMode::Cpu))
+}
+
+/// has_gpu returns whether the server can run requests on a GPU.
+/// Servers that have not reported their capabilities run requests on CPUs, which every server has.
+fn has_gpu(server: &ServerInfo) -> bool {
+    server.node().capabilities.gpu.is_some()
+}
+
+impl ExecutionConfig {
+    pub fn is_saturated(&self, stats: &ServerStats) -> bool {
+        stats
+            .average()
+            .is_some_and(|u| u.gpu >= self.gpu_saturation)
+    }
+}
+
+impl Default for ExecutionConfig {
+    fn default() -> Self {
+        Self {
+            gpu_saturation: 80.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::node::{Capabilities, GpuInfo, NodeInfo};
+    use crate::proto::ResourceUtilization;
+    use crate::scheduler::mean::MeanScheduler;
+    use crate::scheduler::stats::fixture::{map, server, stats_with};
+
+    use super::*;
+
+    fn stats_with_gpu(gpu: i32) -> ServerStats {
+        let capabilities = Capabilities {
+            gpu: Some(GpuInfo::default()),
+            ..Default::default()
+        };
+        let server = server().with_node(NodeInfo::new(Default::default(), capabilities));
+        let utilization = ResourceUtilization {
+            gpu,
+            ..Default::default()
+        };
+
+        stats_with(server, utilization)
+    }
+
+    #[test]
+    fn test_gpu_while_available() {
+        let idle = stats_with_gpu(10);
+        let busy = stats_with_gpu(95);
+        let candidates = map(vec![idle.clone(), busy]);
+
+        let (server, mode) = select_execution(
+            &MeanScheduler::default(),
+            &ExecutionConfig::default(),
+            &candidates,
+            &Constraints::default(),
+            Accuracy::Balanced,
+        )
+        .unwrap();
+
+        assert_eq!(server.id, idle.server.id);
+        assert_eq!(mode, ExecutionMode::Gpu);
+    }
+
+    #[test]
+    fn test_cpu_when_saturated() {
+        let candidates = map(vec![stats_with_gpu(90), stats_with_gpu(95)]);
+        let scheduler = MeanScheduler::default();
+        let config = ExecutionConfig::default();
+
+        let (_, mode) = select_execution(
+            &scheduler,
+            &config,
+            &candidates,
+            &Constraints::default(),
+            Accuracy::Balanced,
+        )
+        .unwrap();
+        assert_eq!(mode, ExecutionMode::Cpu);
+
+        // High accuracy keeps requests on the GPUs
+        let (_, mode) = select_execution(
+            &scheduler,
+            &config,
+            &candidates,
+            &Constraints::default(),
+            Accuracy::High,
+        )
+        .unwrap();
+        assert_eq!(mode, ExecutionMode::Gpu);
+
+        let gpu_required = Constraints {
+            gpu_required: true,
+            ..Default::default()
+        };
+        let (_, mode) = select_execution(
+            &scheduler,
+            &config,
+            &candidates,
+            &gpu_required,
+            Accuracy::Balanced,
+        )
+        .unwrap();
+        assert_eq!(mode, ExecutionMode::Gpu);
+    }
+
+    #[test]
+    fn test_cpu_without_reported_gpu() {
+        let candidates = map(vec![ServerStats::new(server())]);
+
+        let (_, mode) = select_execution(
+            &MeanScheduler::default(),
+            &ExecutionConfig::default(),
+            &candidates,
+            &Constraints::default(),
+            Accuracy::Balanced,
+        )
+        .unwrap();
+        assert_eq!(mode, ExecutionMode::Cpu);
+    }
+}
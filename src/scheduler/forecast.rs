@@ -0,0 +1,211 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::utils::IdMap;
+
+/// RateForecaster tracks the request rate of each deployment, and forecasts the near-term rate
+/// from its trend, so that instances can be spawned before the current ones saturate.
+#[derive(Clone, Debug)]
+pub struct RateForecaster {
+    pub config: ForecastConfig,
+    pub rates: IdMap<RequestRate>,
+    /// When each deployment was scaled out by the forecast the last time
+    pub scaled_out_at: IdMap<Instant>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ForecastConfig {
+    /// Length of an interval in which requests are counted
+    pub interval: Duration,
+    /// How far ahead the rate is forecasted
+    pub horizon: Duration,
+    /// How long a deployment is not scaled again after a scale-out
+    pub cooldown: Duration,
+    /// Smoothing factor of the level, in `0..=1`. Higher follows the latest rate faster.
+    pub alpha: f64,
+    /// Smoothing factor of the trend, in `0..=1`
+    pub beta: f64,
+}
+
+/// RequestRate counts the requests in fixed intervals, and smooths the rates with their trend
+/// by Holt's linear method.
+#[derive(Clone, Debug)]
+pub struct RequestRate {
+    started: Instant,
+    count: u64,
+    /// Smoothed rate in requests per second. `None` until the first interval ends.
+    level: Option<f64>,
+    /// Change of the rate per interval
+    trend: f64,
+}
+
+/// Forecast is the current rate and the forecasted one, in requests per second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Forecast {
+    pub current: f64,
+    pub predicted: f64,
+}
+
+impl RateForecaster {
+    pub fn new(config: ForecastConfig) -> Self {
+        Self {
+            config,
+            rates: IdMap::new(),
+            scaled_out_at: IdMap::new(),
+        }
+    }
+
+    /// record counts a request to the deployment.
+    pub fn record(&mut self, id: Uuid, now: Instant) {
+        let rate = self
+            .rates
+            .0
+            .entry(id)
+            .or_insert_with(|| RequestRate::new(now));
+
+        rate.roll(now, &self.config);
+        rate.count += 1;
+    }
+
+    /// tick closes the intervals that have ended, including those without requests.
+    pub fn tick(&mut self, now: Instant) {
+        for (_, rate) in self.rates.0.iter_mut() {
+            rate.roll(now, &self.config);
+        }
+    }
+
+    pub fn forecast(&self, id: &Uuid) -> Option<Forecast> {
+        let rate = self.rates.0.get(id)?;
+        let level = rate.level?;
+
+        let steps = self.config.horizon.as_secs_f64() / self.config.interval.as_secs_f64();
+        let predicted = (level + rate.trend * steps).max(0.);
+
+        Some(Forecast {
+            current: level,
+            predicted,
+        })
+    }
+
+    pub fn in_cooldown(&self, id: &Uuid, now: Instant) -> bool {
+        self.scaled_out_at
+            .0
+            .get(id)
+            .is_some_and(|at| now.duration_since(*at) < self.config.cooldown)
+    }
+
+    pub fn scaled_out(&mut self, id: Uuid, now: Instant) {
+        self.scaled_out_at.0.insert(id, now);
+    }
+
+    /// remove forgets the deployment.
+    pub fn remove(&mut self, id: &Uuid) {
+        self.rates.0.remove(id);
+        self.scaled_out_at.0.remove(id);
+    }
+}
+
+impl RequestRate {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            count: 0,
+            level: None,
+            trend: 0.,
+        }
+    }
+
+    fn roll(&mut self, now: Instant, config: &ForecastConfig) {
+        while now.duration_since(self.started) >= config.interval {
+            let rate = self.count as f64 / config.interval.as_secs_f64();
+            self.update(rate, config);
+
+            self.started += config.interval;
+            self.count = 0;
+        }
+    }
+
+    fn update(&mut self, rate: f64, config: &ForecastConfig) {
+        let Some(level) = self.level else {
+            self.level = Some(rate);
+            return;
+        };
+
+        let next = config.alpha * rate + (1. - config.alpha) * (level + self.trend);
+        self.trend = config.beta * (next - level) + (1. - config.beta) * self.trend;
+        self.level = Some(next);
+    }
+}
+
+impl Default for ForecastConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            horizon: Duration::from_secs(30),
+            cooldown: Duration::from_secs(60),
+            alpha: 0.5,
+            beta: 0.3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record_per_interval(
+        forecaster: &mut RateForecaster,
+        id: Uuid,
+        start: Instant,
+        counts: &[u64],
+    ) -> Instant {
+        let interval = forecaster.config.interval;
+        let mut now = start;
+
+        for count in counts {
+            for _ in 0..*count {
+                forecaster.record(id, now);
+            }
+            now += interval;
+        }
+
+        forecaster.tick(now);
+        now
+    }
+
+    #[test]
+    fn test_forecasts_rising_rate() {
+        let mut forecaster = RateForecaster::new(ForecastConfig::default());
+        let id = Uuid::new_v4();
+
+        record_per_interval(&mut forecaster, id, Instant::now(), &[10, 20, 30, 40, 50]);
+
+        let forecast = forecaster.forecast(&id).unwrap();
+        assert!(forecast.predicted > forecast.current);
+    }
+
+    #[test]
+    fn test_steady_rate() {
+        let mut forecaster = RateForecaster::new(ForecastConfig::default());
+        let id = Uuid::new_v4();
+
+        record_per_interval(&mut forecaster, id, Instant::now(), &[10; 8]);
+
+        let forecast = forecaster.forecast(&id).unwrap();
+        assert_eq!(forecast.predicted, forecast.current);
+        assert_eq!(forecast.current, 2.);
+    }
+
+    #[test]
+    fn test_cooldown() {
+        let mut forecaster = RateForecaster::new(ForecastConfig::default());
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        forecaster.scaled_out(id, now);
+
+        assert!(forecaster.in_cooldown(&id, now + Duration::from_secs(1)));
+        assert!(!forecaster.in_cooldown(&id, now + forecaster.config.cooldown));
+    }
+}
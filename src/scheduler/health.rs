@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::{utils::IdMap, ServerInfo};
+
+#[derive(Clone, Debug)]
+pub struct FailureDetector {
+    pub config: FailureDetectorConfig,
+    pub servers: IdMap<ServerHealth>,
+}
+
+#[derive(Clone, Debug)]
+pub struct FailureDetectorConfig {
+    /// How often the scheduler checks for silent servers
+    pub interval: Duration,
+    /// A server that has not reported for this duration is pinged
+    pub suspect_after: Duration,
+    /// A server that has not reported nor responded to pings for this duration is removed
+    pub dead_after: Duration,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Liveness {
+    Alive,
+    Suspect,
+    Dead,
+}
+
+#[derive(Clone, Debug)]
+pub struct ServerHealth {
+    pub last_seen: Instant,
+    pub liveness: Liveness,
+}
+
+impl FailureDetector {
+    pub fn new(config: FailureDetectorConfig) -> Self {
+        Self {
+            config,
+            servers: IdMap::new(),
+        }
+    }
+
+    /// heard is called when the server reported, or responded to a ping.
+    pub fn heard(&mut self, id: Uuid, now: Instant) {
+        self.servers.0.insert(
+            id,
+            ServerHealth {
+                last_seen: now,
+                liveness: Liveness::Alive,
+            },
+        );
+    }
+
+    /// silent returns the servers that have not been heard from for `suspect_after`.
+    /// Servers seen for the first time are considered to be heard now.
+    pub fn silent(&mut self, servers: &[ServerInfo], now: Instant) -> Vec<ServerInfo> {
+        servers
+            .iter()
+            .filter(|s| {
+                let health = self.servers.0.entry(s.id).or_insert(ServerHealth {
+                    last_seen: now,
+                    liveness: Liveness::Alive,
+                });
+
+                now.duration_since(health.last_seen) >= self.config.suspect_after
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// suspect is called when a silent server did not respond to a ping.
+    /// Returns `Liveness::Dead` once the server has been silent for `dead_after`.
+    pub fn suspect(&mut self, id: &Uuid, now: Instant) -> Liveness {
+        let dead_after = self.config.dead_after;
+
+        let health = self.servers.0.entry(*id).or_insert(ServerHealth {
+            last_seen: now,
+            liveness: Liveness::Alive,
+        });
+
+        health.liveness = if now.duration_since(health.last_seen) >= dead_after {
+            Liveness::Dead
+        } else {
+            Liveness::Suspect
+        };
+
+        health.liveness
+    }
+
+    pub fn remove(&mut self, id: &Uuid) -> Option<ServerHealth> {
+        self.servers.0.remove(id)
+    }
+}
+
+impl Default for FailureDetectorConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(2),
+            suspect_after: Duration::from_secs(5),
+            dead_after: Duration::from_secs(15),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn detector() -> FailureDetector {
+        FailureDetector::new(FailureDetectorConfig::default())
+    }
+
+    #[test]
+    fn test_silent_after_suspect_duration() {
+        let mut detector = detector();
+        let server = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let servers = vec![server.clone()];
+
+        let start = Instant::now();
+        assert!(detector.silent(&servers, start).is_empty());
+
+        let later = start + detector.config.suspect_after;
+        assert_eq!(detector.silent(&servers, later).len(), 1);
+
+        detector.heard(server.id, later);
+        assert!(detector.silent(&servers, later).is_empty());
+    }
+
+    #[test]
+    fn test_suspect_then_dead() {
+        let mut detector = detector();
+        let id = Uuid::new_v4();
+
+        let start = Instant::now();
+        detector.heard(id, start);
+
+        let suspected = start + detector.config.suspect_after;
+        assert_eq!(detector.suspect(&id, suspected), Liveness::Suspect);
+
+        let dead = start + detector.config.dead_after;
+        assert_eq!(detector.suspect(&id, dead), Liveness::Dead);
+    }
+}
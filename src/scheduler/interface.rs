@@ -1,3 +1,5 @@
+use laqista_core::Constraints;
+
 use crate::ServerInfo;
 
 use super::stats::{ServerStats, StatsMap};
@@ -6,6 +8,19 @@ pub trait DeploymentScheduler: SchedulerClone + std::fmt::Debug + Send + Sync {
     fn schedule(&self, stats: &StatsMap) -> Option<ServerInfo>;
     fn schedule_gpu(&self, stats: &StatsMap) -> Option<ServerInfo>;
     fn needs_scale_out(&self, server: &ServerInfo, stats: &ServerStats) -> bool;
+    fn needs_scale_in(&self, server: &ServerInfo, stats: &ServerStats) -> bool;
+
+    /// score returns the value that `schedule`, or `schedule_gpu` if `gpu`, ranks the server on.
+    /// Policies that do not rank servers return `None`.
+    fn score(&self, _stats: &ServerStats, _gpu: bool) -> Option<f64> {
+        None
+    }
+
+    /// accepts returns whether an instance under `constraints` can be placed on the server.
+    /// By default, the labels and capabilities that the server reported are matched.
+    fn accepts(&self, server: &ServerInfo, constraints: &Constraints) -> bool {
+        server.node().satisfies(constraints)
+    }
 }
 
 pub trait SchedulerClone {
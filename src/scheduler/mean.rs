@@ -1,92 +1,83 @@
-use crate::{utils::mul_as_percent, ServerInfo};
+use tracing::warn;
+
+use crate::ServerInfo;
 
 use super::{
     interface::DeploymentScheduler,
-    stats::{ServerStats, StatsMap},
+    registry::PolicyInfo,
+    stats::{ServerStats, StatsMap, Utilization},
 };
 
 #[derive(Clone, Debug)]
-pub struct MeanScheduler {}
+pub struct MeanScheduler {
+    pub scale_out_threshold: usize,
+    pub scale_in_threshold: usize,
+}
 
-const SCALEOUT_THREASHOLD: usize = 70;
+pub const SCALEOUT_THREASHOLD: usize = 70;
+pub const SCALEIN_THREASHOLD: usize = 20;
 
 impl DeploymentScheduler for MeanScheduler {
     fn schedule(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
-        let mut least_utilized = stats_map
-            .iter()
-            .next()
-            .or_else(|| {
-                println!("WARN: stats are empty");
-                None
-            })?
-            .1;
-        let mut least_utilized_rate = 0.;
-
-        for (_id, stats) in stats_map.iter() {
-            let utilized_rate = self.cpu_utilized_rate(stats);
-
-            if utilized_rate < least_utilized_rate {
-                least_utilized = stats;
-                least_utilized_rate = utilized_rate;
-            }
-        }
-
-        Some(least_utilized.server.clone())
+        self.least_utilized(stats_map, |u| u.cpu)
     }
 
     fn schedule_gpu(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
-        let mut least_utilized = stats_map
-            .iter()
-            .next()
-            .or({
-                println!("WARN: stats are empty");
-                None
-            })?
-            .1;
-        let mut least_utilized_rate = 0.;
-
-        for (_id, stats) in stats_map.iter() {
-            let utilized_rate = self.gpu_utilized_rate(stats);
+        self.least_utilized(stats_map, |u| u.gpu)
+    }
 
-            if utilized_rate < least_utilized_rate {
-                least_utilized = stats;
-                least_utilized_rate = utilized_rate;
-            }
-        }
+    fn needs_scale_out(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu > self.scale_out_threshold as _)
+    }
 
-        Some(least_utilized.server.clone())
+    fn needs_scale_in(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu < self.scale_in_threshold as _)
     }
 
-    fn needs_scale_out(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
-        let stat = match stats.stats.last() {
-            Some(s) => s,
-            None => return false,
-        };
+    fn score(&self, stats: &ServerStats, gpu: bool) -> Option<f64> {
+        let average = stats.average();
+        Some(if gpu {
+            average.map_or(0., |u| u.gpu)
+        } else {
+            average.map_or(0., |u| u.cpu)
+        })
+    }
+}
 
-        return stat.utilization.as_ref().unwrap().cpu > SCALEOUT_THREASHOLD as _;
+impl Default for MeanScheduler {
+    fn default() -> Self {
+        Self::new(&PolicyInfo::default())
     }
 }
 
 impl MeanScheduler {
-    fn gpu_utilized_rate(&self, stats: &ServerStats) -> f64 {
-        let total: f64 = stats.windows().map(|w| w.nanos as f64).sum();
-
-        let utilized: f64 = stats
-            .windows()
-            .map(|w| mul_as_percent(w.nanos, w.utilization.gpu as _) as f64)
-            .sum();
-
-        utilized / total
+    pub fn new(policy: &PolicyInfo) -> Self {
+        Self {
+            scale_out_threshold: policy.scale_out_threshold.unwrap_or(SCALEOUT_THREASHOLD),
+            scale_in_threshold: policy.scale_in_threshold.unwrap_or(SCALEIN_THREASHOLD),
+        }
     }
 
-    fn cpu_utilized_rate(&self, stats: &ServerStats) -> f64 {
-        let total: f64 = stats.windows().map(|w| w.nanos as f64).sum();
-
-        let utilized: f64 = stats
-            .windows()
-            .map(|w| mul_as_percent(w.nanos, w.utilization.cpu as _) as f64)
-            .sum();
+    /// least_utilized returns the server with the lowest average of `resource`.
+    /// Servers that have not reported yet are regarded as idle.
+    fn least_utilized<F>(&self, stats_map: &StatsMap, resource: F) -> Option<ServerInfo>
+    where
+        F: Fn(&Utilization) -> f64,
+    {
+        let least_utilized = stats_map
+            .iter()
+            .map(|(_, stats)| (stats.average().map_or(0., &resource), stats))
+            .min_by(|(a, _), (b, _)| a.total_cmp(b))
+            .or_else(|| {
+                warn!("stats are empty");
+                None
+            })?
+            .1;
 
-        utilized / total
+        Some(least_utilized.server.clone())
     }
 }
@@ -0,0 +1,226 @@
+use laqista_core::Resources;
+
+use crate::ServerInfo;
+
+use super::Cluster;
+
+/// BestFitPlacement places an instance on the server that it fills up the most,
+/// judged by the dominant resource, i.e., the one with the highest share after placement.
+/// Packing instances tightly leaves room for instances with large requests.
+#[derive(Clone, Debug, Default)]
+pub struct BestFitPlacement {}
+
+/// ServerCapacity is the capacity and the usage of a server, for each resource.
+#[derive(Clone, Debug)]
+pub struct ServerCapacity {
+    pub server: ServerInfo,
+    /// `None` if the capacity is not reported
+    pub capacity: [Option<u64>; 4],
+    pub used: [u64; 4],
+}
+
+impl BestFitPlacement {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// place returns the best server for `request` among `candidates`.
+    /// Returns `None` if the instance would exceed the capacity of every candidate.
+    pub fn place(&self, candidates: &[ServerCapacity], request: &Resources) -> Option<ServerInfo> {
+        let mut scored: Vec<_> = candidates
+            .iter()
+            .filter_map(|c| c.dominant_share(request).map(|share| (share, c)))
+            .collect();
+
+        // Keep the order stable among the servers with the same share
+        scored.sort_by(|(a, x), (b, y)| b.total_cmp(a).then(x.server.id.cmp(&y.server.id)));
+
+        scored.first().map(|(_, c)| c.server.clone())
+    }
+}
+
+impl ServerCapacity {
+    /// of returns the capacity of the server in `cluster`.
+    /// Usage is the larger of the requests of the instances on it and the reported utilization.
+    pub fn of(cluster: &Cluster, server: &ServerInfo) -> Self {
+        let mut used = [0; 4];
+        for deployment in cluster.deployments_on(&server.id) {
+            for (u, r) in used.iter_mut().zip(as_array(&deployment.resources)) {
+                *u += r;
+            }
+        }
+
+        // CPU and GPU are in percent of the server.
+        // RAM falls back to the total reported at Join, as monitors may not measure it.
+        let ram_total = server.node().capabilities.ram_total;
+        let mut capacity = [
+            Some(100),
+            (ram_total > 0).then_some(ram_total),
+            Some(100),
+            None,
+        ];
+
+        let latest = cluster
+            .server_stats
+            .0
+            .get(&server.id)
+            .and_then(|s| s.latest());
+
+        if let Some(u) = latest {
+            // Negative values are not reported
+            let known = |v: i32| (v >= 0).then_some(v as u64);
+
+            capacity[1] = known(u.ram_total).or(capacity[1]);
+            capacity[3] = known(u.vram_total).or(capacity[3]);
+
+            let observed = [u.cpu, u.ram_used, u.gpu, u.vram_used];
+            for (u, o) in used.iter_mut().zip(observed) {
+                *u = (*u).max(known(o).unwrap_or(0));
+            }
+        }
+
+        Self {
+            server: server.clone(),
+            capacity,
+            used,
+        }
+    }
+
+    /// dominant_share returns the highest share of the resources after placing `request`.
+    /// Returns `None` if any resource would exceed its capacity, or if the capacity of a
+    /// requested resource is unknown.
+    pub fn dominant_share(&self, request: &Resources) -> Option<f64> {
+        let mut dominant: f64 = 0.;
+
+        for ((capacity, used), requested) in
+            self.capacity.iter().zip(self.used).zip(as_array(request))
+        {
+            if requested == 0 {
+                continue;
+            }
+            let capacity = (*capacity)?;
+
+            let after = used + requested;
+            if after > capacity {
+                return None;
+            }
+
+            dominant = dominant.max(after as f64 / capacity as f64);
+        }
+
+        Some(dominant)
+    }
+}
+
+fn as_array(resources: &Resources) -> [u64; 4] {
+    [
+        resources.cpu as _,
+        resources.ram as _,
+        resources.gpu as _,
+        resources.vram as _,
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use uuid::Uuid;
+
+    use crate::node::{Capabilities, NodeInfo};
+    use crate::proto::{MonitorWindow, ResourceUtilization};
+    use crate::scheduler::stats::ServerStats;
+
+    use super::*;
+
+    fn capacity(cpu_used: u64, ram: Option<(u64, u64)>) -> ServerCapacity {
+        let server = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let (ram_capacity, ram_used) = match ram {
+            Some((total, used)) => (Some(total), used),
+            None => (None, 0),
+        };
+
+        ServerCapacity {
+            server,
+            capacity: [Some(100), ram_capacity, Some(100), None],
+            used: [cpu_used, ram_used, 0, 0],
+        }
+    }
+
+    #[test]
+    fn test_best_fit() {
+        let empty = capacity(0, Some((1000, 0)));
+        let half = capacity(50, Some((1000, 500)));
+        let full = capacity(90, Some((1000, 500)));
+
+        let request = Resources {
+            cpu: 20,
+            ram: 100,
+            ..Default::default()
+        };
+
+        let placed = BestFitPlacement::new()
+            .place(&[empty, half.clone(), full], &request)
+            .unwrap();
+
+        // `full` does not have enough CPU, and `half` fits tighter than `empty`
+        assert_eq!(placed.id, half.server.id);
+    }
+
+    #[test]
+    fn test_ram_capacity_from_node() {
+        let node = NodeInfo {
+            capabilities: Capabilities {
+                ram_total: 1000,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4()).with_node(node);
+
+        // The monitor does not measure RAM
+        let window = MonitorWindow {
+            window: None,
+            utilization: Some(ResourceUtilization {
+                cpu: 10,
+                ram_total: -1,
+                ram_used: -1,
+                vram_total: -1,
+                vram_used: -1,
+                ..Default::default()
+            }),
+        };
+        let mut cluster = Cluster::new(&server);
+        cluster.insert_stats(ServerStats::from_stats(server.clone(), vec![window]));
+
+        let capacity = ServerCapacity::of(&cluster, &server);
+        let ram = |ram| Resources {
+            ram,
+            ..Default::default()
+        };
+
+        assert_eq!(capacity.capacity[1], Some(1000));
+        assert!(capacity.dominant_share(&ram(500)).is_some());
+        assert!(capacity.dominant_share(&ram(2000)).is_none());
+
+        // VRAM is neither measured nor reported at Join
+        let vram = Resources {
+            vram: 1,
+            ..Default::default()
+        };
+        assert!(capacity.dominant_share(&vram).is_none());
+    }
+
+    #[test]
+    fn test_refuses_over_capacity() {
+        let small = capacity(0, Some((100, 0)));
+        let unknown = capacity(90, None);
+
+        let request = Resources {
+            cpu: 20,
+            ram: 200,
+            ..Default::default()
+        };
+
+        let placed = BestFitPlacement::new().place(&[small, unknown], &request);
+        assert!(placed.is_none());
+    }
+}
@@ -0,0 +1,128 @@
+use tracing::warn;
+
+use crate::{utils::random_below, ServerInfo};
+
+use super::{
+    interface::DeploymentScheduler,
+    mean::{SCALEIN_THREASHOLD, SCALEOUT_THREASHOLD},
+    registry::PolicyInfo,
+    stats::{ServerStats, StatsMap},
+};
+
+/// PowerOfTwoScheduler samples two servers at random, and picks the less loaded one.
+/// Unlike picking the least loaded server, concurrent decisions do not herd onto one server.
+#[derive(Clone, Debug)]
+pub struct PowerOfTwoScheduler {
+    pub scale_out_threshold: usize,
+    pub scale_in_threshold: usize,
+}
+
+impl PowerOfTwoScheduler {
+    pub fn new(policy: &PolicyInfo) -> Self {
+        Self {
+            scale_out_threshold: policy.scale_out_threshold.unwrap_or(SCALEOUT_THREASHOLD),
+            scale_in_threshold: policy.scale_in_threshold.unwrap_or(SCALEIN_THREASHOLD),
+        }
+    }
+
+    fn pick<F>(&self, stats_map: &StatsMap, load: F) -> Option<ServerInfo>
+    where
+        F: Fn(&ServerStats) -> i32,
+    {
+        let candidates: Vec<_> = stats_map.iter().map(|(_, s)| s).collect();
+
+        let (a, b) = match candidates.len() {
+            0 => {
+                warn!("stats are empty");
+                return None;
+            }
+            1 => (candidates[0], candidates[0]),
+            n => {
+                let i = random_below(n);
+                // Pick another one from the rest
+                let j = (i + 1 + random_below(n - 1)) % n;
+                (candidates[i], candidates[j])
+            }
+        };
+
+        let picked = if load(b) < load(a) { b } else { a };
+        Some(picked.server.clone())
+    }
+}
+
+impl DeploymentScheduler for PowerOfTwoScheduler {
+    fn schedule(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
+        self.pick(stats_map, |s| load(s, false))
+    }
+
+    fn schedule_gpu(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
+        self.pick(stats_map, |s| load(s, true))
+    }
+
+    fn needs_scale_out(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu > self.scale_out_threshold as _)
+    }
+
+    fn needs_scale_in(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu < self.scale_in_threshold as _)
+    }
+
+    fn score(&self, stats: &ServerStats, gpu: bool) -> Option<f64> {
+        Some(load(stats, gpu) as _)
+    }
+}
+
+/// load is the utilization in the latest window, which the two samples are compared on.
+fn load(stats: &ServerStats, gpu: bool) -> i32 {
+    stats
+        .latest()
+        .map_or(0, |u| if gpu { u.gpu } else { u.cpu })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scheduler::stats::fixture::{map, stats_with_cpu};
+
+    use super::*;
+
+    fn scheduler() -> PowerOfTwoScheduler {
+        PowerOfTwoScheduler::new(&PolicyInfo::new("power-of-two"))
+    }
+
+    #[test]
+    fn test_picks_less_loaded_of_two() {
+        let idle = stats_with_cpu(10);
+        let busy = stats_with_cpu(90);
+        let map = map(vec![idle.clone(), busy]);
+
+        for _ in 0..20 {
+            assert_eq!(scheduler().schedule(&map).unwrap().id, idle.server.id);
+        }
+    }
+
+    #[test]
+    fn test_never_picks_most_loaded() {
+        let stats = vec![stats_with_cpu(10), stats_with_cpu(50), stats_with_cpu(90)];
+        let busiest = stats[2].server.id;
+        let map = map(stats);
+
+        for _ in 0..100 {
+            assert_ne!(scheduler().schedule(&map).unwrap().id, busiest);
+        }
+    }
+
+    #[test]
+    fn test_scale_thresholds() {
+        let scheduler = scheduler();
+        let busy = stats_with_cpu(90);
+        let idle = stats_with_cpu(10);
+
+        assert!(scheduler.needs_scale_out(&busy.server, &busy));
+        assert!(!scheduler.needs_scale_out(&idle.server, &idle));
+        assert!(scheduler.needs_scale_in(&idle.server, &idle));
+    }
+}
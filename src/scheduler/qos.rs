@@ -0,0 +1,121 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+
+use crate::proto::{Accuracy, QoS};
+use crate::utils::IdMap;
+
+use super::stats::StatsMap;
+
+#[derive(Clone, Debug, Default)]
+pub struct QosInfo {
+    pub latency_target: Option<Duration>,
+    pub accuracy: Accuracy,
+}
+
+impl QosInfo {
+    /// within_target returns the servers whose recent p95 latency of the deployment
+    /// is within the latency target.
+    /// Servers that have not served the deployment yet are assumed to be within it.
+    pub fn within_target(&self, deployment_id: &Uuid, stats_map: &StatsMap) -> StatsMap {
+        let Some(target) = self.latency_target else {
+            return stats_map.clone();
+        };
+
+        let map = stats_map
+            .iter()
+            .filter(|(_, stats)| {
+                stats
+                    .p95_latency(deployment_id)
+                    .map_or(true, |p95| p95 <= target)
+            })
+            .map(|(id, stats)| (*id, stats.clone()))
+            .collect();
+
+        IdMap(map)
+    }
+}
+
+/// fastest returns the stats of the server with the lowest p95 latency of the deployment.
+pub fn fastest(deployment_id: &Uuid, stats_map: &StatsMap) -> StatsMap {
+    let map = stats_map
+        .iter()
+        .min_by_key(|(_, stats)| stats.p95_latency(deployment_id).unwrap_or_default())
+        .map(|(id, stats)| (*id, stats.clone()))
+        .into_iter()
+        .collect();
+
+    IdMap(map)
+}
+
+impl From<QoS> for QosInfo {
+    fn from(qos: QoS) -> Self {
+        let accuracy = qos.accuracy();
+        let latency_target = qos
+            .latency_target_ms
+            .map(|ms| Duration::from_millis(ms as _));
+
+        Self {
+            latency_target,
+            accuracy,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scheduler::stats::fixture::{map as stats_map, server};
+    use crate::scheduler::stats::{LatencyStats, ServerStats};
+
+    use super::*;
+
+    fn stats_with_latency(deployment_id: Uuid, p95: Option<Duration>) -> ServerStats {
+        let mut stats = ServerStats::new(server());
+
+        if let Some(p95) = p95 {
+            stats.update_latencies(vec![LatencyStats {
+                deployment_id,
+                p95,
+                count: 1,
+            }]);
+        }
+
+        stats
+    }
+
+    #[test]
+    fn test_within_target() {
+        let id = Uuid::new_v4();
+        let fast = stats_with_latency(id, Some(Duration::from_millis(10)));
+        let slow = stats_with_latency(id, Some(Duration::from_millis(100)));
+        let unknown = stats_with_latency(id, None);
+
+        let map = stats_map(vec![fast.clone(), slow.clone(), unknown.clone()]);
+
+        let qos = QosInfo {
+            latency_target: Some(Duration::from_millis(50)),
+            ..Default::default()
+        };
+        let within = qos.within_target(&id, &map);
+
+        assert_eq!(within.0.len(), 2);
+        assert!(within.0.contains_key(&fast.server.id));
+        assert!(within.0.contains_key(&unknown.server.id));
+
+        let no_target = QosInfo::default().within_target(&id, &map);
+        assert_eq!(no_target.0.len(), 3);
+    }
+
+    #[test]
+    fn test_fastest() {
+        let id = Uuid::new_v4();
+        let fast = stats_with_latency(id, Some(Duration::from_millis(10)));
+        let slow = stats_with_latency(id, Some(Duration::from_millis(100)));
+
+        let map = stats_map(vec![slow, fast.clone()]);
+
+        let fastest = fastest(&id, &map);
+        assert_eq!(fastest.0.len(), 1);
+        assert!(fastest.0.contains_key(&fast.server.id));
+    }
+}
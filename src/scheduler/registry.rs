@@ -0,0 +1,119 @@
+use std::time::Duration;
+
+use crate::proto::SchedulerPolicy;
+use crate::{Error, Result};
+
+use super::interface::DeploymentScheduler;
+use super::mean::MeanScheduler;
+use super::power_of_two::PowerOfTwoScheduler;
+use super::round_robin::RoundRobinScheduler;
+use super::stats::StatsConfig;
+
+pub const DEFAULT_SCHEDULER: &'static str = "mean";
+
+type Constructor = fn(&PolicyInfo) -> Box<dyn DeploymentScheduler>;
+
+/// Schedulers that can be selected by name
+const SCHEDULERS: &[(&'static str, Constructor)] = &[
+    ("mean", |p| Box::new(MeanScheduler::new(p))),
+    ("round-robin", |p| Box::new(RoundRobinScheduler::new(p))),
+    ("power-of-two", |p| Box::new(PowerOfTwoScheduler::new(p))),
+];
+
+/// PolicyInfo selects a `DeploymentScheduler` implementation and its parameters.
+/// It is a part of the cluster, so that every scheduler of the cluster uses the same policy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PolicyInfo {
+    pub name: String,
+    pub scale_out_threshold: Option<usize>,
+    pub scale_in_threshold: Option<usize>,
+    pub stats_retention: Option<usize>,
+    pub stats_half_life: Option<Duration>,
+}
+
+impl PolicyInfo {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            scale_out_threshold: None,
+            scale_in_threshold: None,
+            stats_retention: None,
+            stats_half_life: None,
+        }
+    }
+
+    /// stats_config returns how long the stats of each server are kept.
+    pub fn stats_config(&self) -> StatsConfig {
+        let default = StatsConfig::default();
+        StatsConfig {
+            retention: self.stats_retention.unwrap_or(default.retention),
+            half_life: self.stats_half_life.unwrap_or(default.half_life),
+        }
+    }
+
+    /// create instantiates the scheduler of this policy.
+    pub fn create(&self) -> Result<Box<dyn DeploymentScheduler>> {
+        let (_, constructor) = SCHEDULERS
+            .iter()
+            .find(|(name, _)| name == &self.name)
+            .ok_or(format!(
+                "unknown scheduler '{}'. Available: {}",
+                self.name,
+                names().join(", ")
+            ))?;
+
+        Ok(constructor(self))
+    }
+}
+
+pub fn names() -> Vec<&'static str> {
+    SCHEDULERS.iter().map(|(name, _)| *name).collect()
+}
+
+impl Default for PolicyInfo {
+    fn default() -> Self {
+        Self::new(DEFAULT_SCHEDULER)
+    }
+}
+
+impl Into<SchedulerPolicy> for PolicyInfo {
+    fn into(self) -> SchedulerPolicy {
+        SchedulerPolicy {
+            name: self.name,
+            scale_out_threshold: self.scale_out_threshold.map(|t| t as _),
+            scale_in_threshold: self.scale_in_threshold.map(|t| t as _),
+            stats_retention: self.stats_retention.map(|r| r as _),
+            stats_half_life_ms: self.stats_half_life.map(|h| h.as_millis() as _),
+        }
+    }
+}
+
+impl TryFrom<SchedulerPolicy> for PolicyInfo {
+    type Error = Error;
+    fn try_from(policy: SchedulerPolicy) -> Result<Self> {
+        if policy.name.is_empty() {
+            Err("Scheduler name cannot be empty".to_owned())?;
+        }
+
+        Ok(Self {
+            name: policy.name,
+            scale_out_threshold: policy.scale_out_threshold.map(|t| t as _),
+            scale_in_threshold: policy.scale_in_threshold.map(|t| t as _),
+            stats_retention: policy.stats_retention.map(|r| r as _),
+            stats_half_life: policy.stats_half_life_ms.map(Duration::from_millis),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_create_by_name() {
+        for name in names() {
+            assert!(PolicyInfo::new(name).create().is_ok());
+        }
+        assert!(PolicyInfo::new("unknown").create().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tracing::warn;
+
+use crate::ServerInfo;
+
+use super::{
+    interface::DeploymentScheduler,
+    mean::{SCALEIN_THREASHOLD, SCALEOUT_THREASHOLD},
+    registry::PolicyInfo,
+    stats::{ServerStats, StatsMap},
+};
+
+/// RoundRobinScheduler picks the servers in turn, regardless of their load.
+#[derive(Clone, Debug)]
+pub struct RoundRobinScheduler {
+    /// Shared among clones, so that the turn is kept while the runtime is cloned
+    next: Arc<AtomicUsize>,
+    pub scale_out_threshold: usize,
+    pub scale_in_threshold: usize,
+}
+
+impl RoundRobinScheduler {
+    pub fn new(policy: &PolicyInfo) -> Self {
+        Self {
+            next: Arc::new(AtomicUsize::new(0)),
+            scale_out_threshold: policy.scale_out_threshold.unwrap_or(SCALEOUT_THREASHOLD),
+            scale_in_threshold: policy.scale_in_threshold.unwrap_or(SCALEIN_THREASHOLD),
+        }
+    }
+
+    fn next_of(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
+        // Sort to keep the order stable across maps of the same servers
+        let mut servers: Vec<_> = stats_map.iter().map(|(_, s)| &s.server).collect();
+        servers.sort_by_key(|s| s.id);
+
+        if servers.is_empty() {
+            warn!("stats are empty");
+            return None;
+        }
+
+        let turn = self.next.fetch_add(1, Ordering::Relaxed);
+        Some(servers[turn % servers.len()].clone())
+    }
+}
+
+impl DeploymentScheduler for RoundRobinScheduler {
+    fn schedule(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
+        self.next_of(stats_map)
+    }
+
+    fn schedule_gpu(&self, stats_map: &StatsMap) -> Option<ServerInfo> {
+        self.next_of(stats_map)
+    }
+
+    fn needs_scale_out(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu > self.scale_out_threshold as _)
+    }
+
+    fn needs_scale_in(&self, _server: &ServerInfo, stats: &ServerStats) -> bool {
+        stats
+            .latest()
+            .map_or(false, |u| u.cpu < self.scale_in_threshold as _)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scheduler::stats::fixture::{map, server};
+
+    use super::*;
+
+    fn stats_map(n: usize) -> StatsMap {
+        map((0..n).map(|_| ServerStats::new(server())).collect())
+    }
+
+    #[test]
+    fn test_schedule_in_turn() {
+        let scheduler = RoundRobinScheduler::new(&PolicyInfo::new("round-robin"));
+        let map = stats_map(3);
+
+        let picked: Vec<_> = (0..4)
+            .map(|_| scheduler.schedule(&map).unwrap().id)
+            .collect();
+
+        assert_ne!(picked[0], picked[1]);
+        assert_ne!(picked[1], picked[2]);
+        assert_ne!(picked[0], picked[2]);
+        assert_eq!(picked[0], picked[3]);
+    }
+
+    #[test]
+    fn test_clones_share_turn() {
+        let scheduler = RoundRobinScheduler::new(&PolicyInfo::new("round-robin"));
+        let cloned = scheduler.clone();
+        let map = stats_map(2);
+
+        let first = scheduler.schedule(&map).unwrap().id;
+        let second = cloned.schedule(&map).unwrap().id;
+
+        assert_ne!(first, second);
+    }
+}
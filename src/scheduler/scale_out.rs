@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::utils::IdMap;
+
+/// ScaleOutTracker keeps at most one scale-out in flight for each deployment,
+/// and rate-limits them by a cooldown.
+#[derive(Clone, Debug)]
+pub struct ScaleOutTracker {
+    pub config: ScaleOutConfig,
+    pub states: IdMap<ScaleOutState>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ScaleOutConfig {
+    /// How long a deployment is not scaled out again after a scale-out
+    pub cooldown: Duration,
+    /// A scale-out in flight for this duration is regarded as lost
+    pub timeout: Duration,
+    /// Number of instances of a deployment, above which it is not scaled out.
+    /// The `max_replicas` constraint of the deployment applies as well.
+    pub max_replicas: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScaleOutState {
+    InProgress { since: Instant },
+    CoolingDown { since: Instant },
+}
+
+impl ScaleOutTracker {
+    pub fn new(config: ScaleOutConfig) -> Self {
+        Self {
+            config,
+            states: IdMap::new(),
+        }
+    }
+
+    /// begin marks a scale-out of the deployment in flight, and returns when it began.
+    /// Returns `None` if another one is in flight or the deployment is cooling down.
+    pub fn begin(&mut self, id: Uuid, now: Instant) -> Option<Instant> {
+        if self.is_busy(&id, now) {
+            return None;
+        }
+
+        self.states
+            .0
+            .insert(id, ScaleOutState::InProgress { since: now });
+        Some(now)
+    }
+
+    /// begin_recovery works the same as `begin`, except for this ignores the cooldown,
+    /// so that instances lost with a server are re-placed right after a scale-out.
+    pub fn begin_recovery(&mut self, id: Uuid, now: Instant) -> Option<Instant> {
+        if self.is_in_flight(&id, now) {
+            return None;
+        }
+
+        self.states
+            .0
+            .insert(id, ScaleOutState::InProgress { since: now });
+        Some(now)
+    }
+
+    /// end finishes the scale-out that began at `began`, and starts the cooldown.
+    /// The cooldown applies to failed ones too, so that a failing spawn is not retried at once.
+    /// Nothing changes if that scale-out has timed out and another one is in flight,
+    /// or if the deployment has been removed meanwhile.
+    pub fn end(&mut self, id: Uuid, began: Instant, now: Instant) {
+        let Some(state) = self.states.0.get_mut(&id) else {
+            return;
+        };
+
+        if *state == (ScaleOutState::InProgress { since: began }) {
+            *state = ScaleOutState::CoolingDown { since: now };
+        }
+    }
+
+    pub fn is_busy(&self, id: &Uuid, now: Instant) -> bool {
+        match self.states.0.get(id) {
+            Some(ScaleOutState::CoolingDown { since }) => {
+                now.duration_since(*since) < self.config.cooldown
+            }
+            _ => self.is_in_flight(id, now),
+        }
+    }
+
+    /// is_in_flight returns whether a scale-out of the deployment is in flight and not lost.
+    pub fn is_in_flight(&self, id: &Uuid, now: Instant) -> bool {
+        match self.states.0.get(id) {
+            Some(ScaleOutState::InProgress { since }) => {
+                now.duration_since(*since) < self.config.timeout
+            }
+            _ => false,
+        }
+    }
+
+    /// allows returns whether the cluster-wide limit allows another instance.
+    pub fn allows(&self, count: usize) -> bool {
+        self.config.max_replicas.map_or(true, |max| count < max)
+    }
+
+    pub fn remove(&mut self, id: &Uuid) {
+        self.states.0.remove(id);
+    }
+}
+
+impl Default for ScaleOutConfig {
+    fn default() -> Self {
+        Self {
+            cooldown: Duration::from_secs(30),
+            timeout: Duration::from_secs(120),
+            max_replicas: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_one_in_flight() {
+        let mut tracker = ScaleOutTracker::new(ScaleOutConfig::default());
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        assert!(tracker.begin(id, now).is_some());
+        assert!(tracker.begin(id, now).is_none());
+
+        // Other deployments are not affected
+        assert!(tracker.begin(Uuid::new_v4(), now).is_some());
+    }
+
+    #[test]
+    fn test_cooldown_and_timeout() {
+        let config = ScaleOutConfig::default();
+        let mut tracker = ScaleOutTracker::new(config.clone());
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        let began = tracker.begin(id, now).unwrap();
+        tracker.end(id, began, now);
+        assert!(tracker.begin(id, now + config.cooldown / 2).is_none());
+        assert!(tracker.begin(id, now + config.cooldown).is_some());
+
+        // A lost scale-out does not block forever
+        assert!(tracker
+            .begin(id, now + config.cooldown + config.timeout)
+            .is_some());
+    }
+
+    #[test]
+    fn test_late_end_keeps_newer_scale_out() {
+        let config = ScaleOutConfig::default();
+        let mut tracker = ScaleOutTracker::new(config.clone());
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        let lost = tracker.begin(id, now).unwrap();
+        let newer = tracker.begin(id, now + config.timeout).unwrap();
+
+        // The lost one finishes late, while the newer one is still in flight
+        tracker.end(id, lost, now + config.timeout);
+        assert_eq!(
+            tracker.states.0[&id],
+            ScaleOutState::InProgress { since: newer }
+        );
+
+        tracker.end(id, newer, now + config.timeout);
+        assert!(tracker.is_busy(&id, now + config.timeout));
+    }
+
+    #[test]
+    fn test_recovery_ignores_cooldown() {
+        let config = ScaleOutConfig::default();
+        let mut tracker = ScaleOutTracker::new(config.clone());
+        let id = Uuid::new_v4();
+        let now = Instant::now();
+
+        let began = tracker.begin(id, now).unwrap();
+        assert!(tracker.begin_recovery(id, now).is_none());
+
+        tracker.end(id, began, now);
+        assert!(tracker.begin(id, now).is_none());
+        assert!(tracker.begin_recovery(id, now).is_some());
+    }
+}
@@ -0,0 +1,490 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+use laqista_core::DeploymentInfo;
+use prost_types::Timestamp;
+use uuid::Uuid;
+
+use crate::proto::{MonitorWindow, ResourceUtilization, TimeWindow};
+use crate::utils::{IdMap, Rng};
+use crate::ServerInfo;
+
+use super::interface::DeploymentScheduler;
+use super::placement::BestFitPlacement;
+use super::scale_out::{ScaleOutConfig, ScaleOutTracker};
+use super::stats::{ServerStats, StatsMap};
+use super::store::StateOp;
+use super::Cluster;
+
+/// Trace is the input of a simulation: the background utilization of each server,
+/// and the requests sent to the scheduler.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    /// Length of a monitor window
+    pub window: Duration,
+    /// Utilization of each server in each window, without the simulated requests
+    pub servers: Vec<Vec<ResourceUtilization>>,
+    /// Requests to the scheduler, sorted by time
+    pub requests: Vec<TimedRequest>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimedRequest {
+    /// Time since the start of the trace
+    pub at: Duration,
+    pub request: SimRequest,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SimRequest {
+    Deploy(DeploymentInfo),
+    Lookup(Uuid),
+}
+
+/// SyntheticConfig generates a trace where a few deployments receive most of the lookups.
+#[derive(Clone, Debug)]
+pub struct SyntheticConfig {
+    pub servers: usize,
+    pub windows: usize,
+    pub window: Duration,
+    pub deployments: usize,
+    pub lookups_per_window: usize,
+    /// The same seed generates the same trace
+    pub seed: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SimulationConfig {
+    /// CPU utilization in percent that a lookup adds to the chosen server, for a window
+    pub cost_per_request: f64,
+    pub scale_out: ScaleOutConfig,
+}
+
+/// Simulation replays a trace against a `DeploymentScheduler` in virtual time,
+/// without any server or network.
+pub struct Simulation {
+    config: SimulationConfig,
+    scheduler: Box<dyn DeploymentScheduler>,
+    cluster: Cluster,
+    scale_out: ScaleOutTracker,
+    placement: BestFitPlacement,
+    /// Requests routed to each server in the current window
+    assigned: IdMap<usize>,
+    report: SimulationReport,
+    /// Sum of the CPU utilization and of its standard deviation, over windows
+    cpu_sum: f64,
+    deviation_sum: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationReport {
+    pub deployments: usize,
+    pub lookups: usize,
+    /// Lookups that could not be routed, e.g., to a deployment without instances
+    pub failed_lookups: usize,
+    /// Deployments and scale-outs for which no server could be chosen
+    pub failed_placements: usize,
+    pub scale_outs: usize,
+    /// Share of the decisions that chose the least utilized candidate, in `0..=1`
+    pub placement_quality: f64,
+    /// Mean CPU utilization of the servers, in percent
+    pub mean_utilization: f64,
+    /// Mean over windows of the standard deviation of CPU utilization among servers
+    pub imbalance: f64,
+    /// Windows in which any server was asked for more than its whole CPU
+    pub overloaded_windows: usize,
+    pub windows: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    /// Windows end before requests at the same time, as such requests belong to the next window
+    Window(usize),
+    Request(usize),
+}
+
+impl Simulation {
+    pub fn new(scheduler: Box<dyn DeploymentScheduler>, config: SimulationConfig) -> Self {
+        let scale_out = ScaleOutTracker::new(config.scale_out.clone());
+        let placeholder = ServerInfo::with_id("127.0.0.1:0", Uuid::nil());
+
+        Self {
+            config,
+            scheduler,
+            cluster: Cluster::new(&placeholder),
+            scale_out,
+            placement: BestFitPlacement::new(),
+            assigned: IdMap::new(),
+            report: SimulationReport::default(),
+            cpu_sum: 0.,
+            deviation_sum: 0.,
+        }
+    }
+
+    /// run replays `trace`, and returns the measurements.
+    pub fn run(mut self, trace: &Trace) -> SimulationReport {
+        let servers: Vec<_> = (0..trace.servers.len())
+            .map(|i| ServerInfo::with_id(&format!("127.0.0.1:{}", 50051 + i), Uuid::new_v4()))
+            .collect();
+
+        let Some(first) = servers.first() else {
+            return self.report;
+        };
+        self.cluster = Cluster::new(first);
+        for server in &servers[1..] {
+            self.cluster.apply(StateOp::Join(server.clone()));
+        }
+        // Servers report right after they join, before any window has been measured
+        for server in &servers {
+            self.cluster
+                .insert_stats(ServerStats::from_stats(server.clone(), vec![]));
+        }
+
+        let windows = trace.servers.iter().map(Vec::len).min().unwrap_or(0);
+
+        let mut events = BinaryHeap::new();
+        // Each window is reported when it ends, so the last one is flushed before the results
+        for index in 0..windows {
+            let end = trace.window * (index as u32 + 1);
+            events.push(Reverse((end, Event::Window(index))));
+        }
+        for (index, request) in trace.requests.iter().enumerate() {
+            events.push(Reverse((request.at, Event::Request(index))));
+        }
+
+        // Virtual time is mapped onto `Instant`, which the scale-out tracker works with
+        let origin = Instant::now();
+        let mut decisions = 0;
+        let mut best_decisions = 0;
+
+        while let Some(Reverse((at, event))) = events.pop() {
+            match event {
+                Event::Window(index) => self.end_window(trace, &servers, index, at),
+                Event::Request(index) => {
+                    let now = origin + at;
+                    if let Some(best) = self.handle(&trace.requests[index].request, now) {
+                        decisions += 1;
+                        best_decisions += best as usize;
+                    }
+                }
+            }
+        }
+
+        let report = &mut self.report;
+        report.windows = windows;
+        if windows > 0 {
+            report.mean_utilization = self.cpu_sum / (windows * servers.len()) as f64;
+            report.imbalance = self.deviation_sum / windows as f64;
+        }
+        if decisions > 0 {
+            report.placement_quality = best_decisions as f64 / decisions as f64;
+        }
+
+        self.report
+    }
+
+    /// end_window reports the utilization of every server in the window that ends at `end`.
+    fn end_window(&mut self, trace: &Trace, servers: &[ServerInfo], index: usize, end: Duration) {
+        let start = end.saturating_sub(trace.window);
+
+        let cpus: Vec<f64> = servers
+            .iter()
+            .zip(&trace.servers)
+            .map(|(server, background)| {
+                let requests = self.assigned.0.remove(&server.id).unwrap_or(0);
+                background[index].cpu as f64 + requests as f64 * self.config.cost_per_request
+            })
+            .collect();
+
+        if cpus.iter().any(|cpu| *cpu > 100.) {
+            self.report.overloaded_windows += 1;
+        }
+
+        let mean = cpus.iter().sum::<f64>() / cpus.len() as f64;
+        let variance = cpus.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / cpus.len() as f64;
+        self.cpu_sum += cpus.iter().map(|c| c.min(100.)).sum::<f64>();
+        self.deviation_sum += variance.sqrt();
+
+        for ((server, background), cpu) in servers.iter().zip(&trace.servers).zip(cpus) {
+            let utilization = ResourceUtilization {
+                cpu: cpu.min(100.) as _,
+                ..background[index].clone()
+            };
+            let window = MonitorWindow {
+                window: Some(TimeWindow {
+                    start: Some(timestamp(start)),
+                    end: Some(timestamp(end)),
+                }),
+                utilization: Some(utilization),
+            };
+
+            let stats = ServerStats::from_stats(server.clone(), vec![window]);
+            self.cluster.insert_stats(stats);
+        }
+    }
+
+    /// handle processes a request, and returns whether it chose the least utilized candidate.
+    fn handle(&mut self, request: &SimRequest, now: Instant) -> Option<bool> {
+        match request {
+            SimRequest::Deploy(deployment) => {
+                self.report.deployments += 1;
+                self.place(deployment)
+            }
+            SimRequest::Lookup(id) => {
+                self.report.lookups += 1;
+
+                let Some(instances) = self.cluster.instances.0.get(id) else {
+                    self.report.failed_lookups += 1;
+                    return None;
+                };
+                let deployment = instances.deployment.clone();
+                let server_ids: Vec<_> = instances.servers.iter().map(|s| s.id).collect();
+                let candidates = self.cluster.server_stats.clone_by_ids(&server_ids);
+
+                let Some(target) = self.schedule(&candidates, &deployment) else {
+                    self.report.failed_lookups += 1;
+                    return None;
+                };
+                *self.assigned.0.entry(target.id).or_insert(0) += 1;
+
+                let needs_scale_out = candidates
+                    .0
+                    .get(&target.id)
+                    .is_some_and(|s| self.scheduler.needs_scale_out(&target, s));
+                let began = needs_scale_out
+                    .then(|| self.scale_out.begin(*id, now))
+                    .flatten();
+                if let Some(began) = began {
+                    self.report.scale_outs += 1;
+                    self.place(&deployment);
+                    self.scale_out.end(*id, began, now);
+                }
+
+                Some(is_least_utilized(&target, &candidates))
+            }
+        }
+    }
+
+    /// place spawns an instance of the deployment on the server chosen by the scheduler.
+    fn place(&mut self, deployment: &DeploymentInfo) -> Option<bool> {
+        let candidates = self.cluster.placement_candidates(&deployment.id, |s| {
+            self.scheduler.accepts(s, &deployment.constraints)
+        });
+
+        let placed =
+            self.cluster
+                .place_instance(self.scheduler.as_ref(), &self.placement, deployment);
+        let Ok(target) = placed else {
+            self.report.failed_placements += 1;
+            return None;
+        };
+
+        let best = is_least_utilized(&target, &candidates);
+        self.cluster
+            .apply(StateOp::AddInstance(deployment.clone(), target));
+
+        Some(best)
+    }
+
+    fn schedule(&self, candidates: &StatsMap, deployment: &DeploymentInfo) -> Option<ServerInfo> {
+        if deployment.constraints.gpu_required {
+            self.scheduler.schedule_gpu(candidates)
+        } else {
+            self.scheduler.schedule(candidates)
+        }
+    }
+}
+
+/// is_least_utilized returns whether no candidate had a lower CPU utilization than `target`.
+fn is_least_utilized(target: &ServerInfo, candidates: &StatsMap) -> bool {
+    let cpu = |s: &ServerStats| s.latest().map_or(0, |u| u.cpu);
+
+    let Some(chosen) = candidates.0.get(&target.id) else {
+        return candidates.0.is_empty();
+    };
+
+    candidates.iter().all(|(_, s)| cpu(chosen) <= cpu(s))
+}
+
+fn timestamp(at: Duration) -> Timestamp {
+    Timestamp {
+        seconds: at.as_secs() as _,
+        nanos: at.subsec_nanos() as _,
+    }
+}
+
+impl Trace {
+    /// from_windows creates a trace from the windows recorded on each server,
+    /// e.g., by the Monitor RPC. Windows without utilization are regarded as idle.
+    pub fn from_windows(
+        window: Duration,
+        servers: Vec<Vec<MonitorWindow>>,
+        requests: Vec<TimedRequest>,
+    ) -> Self {
+        let servers = servers
+            .into_iter()
+            .map(|windows| {
+                windows
+                    .into_iter()
+                    .map(|w| w.utilization.unwrap_or_default())
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            window,
+            servers,
+            requests,
+        }
+    }
+
+    pub fn synthetic(config: &SyntheticConfig) -> Self {
+        let mut rng = Rng::new(config.seed);
+
+        // Background load wanders around between 5% and 40%
+        let servers = (0..config.servers)
+            .map(|_| {
+                let mut cpu = 5 + rng.below(35) as i32;
+                (0..config.windows)
+                    .map(|_| {
+                        cpu = (cpu + rng.below(11) as i32 - 5).clamp(5, 40);
+                        ResourceUtilization {
+                            cpu,
+                            ..Default::default()
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let deployments: Vec<_> = (0..config.deployments)
+            .map(|i| {
+                let name = format!("app-{i}");
+                let source = format!("https://example.com/{name}.tgz");
+                DeploymentInfo {
+                    id: rng.uuid(),
+                    ..DeploymentInfo::new(name, source)
+                }
+            })
+            .collect();
+
+        let mut requests: Vec<_> = deployments
+            .iter()
+            .map(|d| TimedRequest {
+                at: Duration::ZERO,
+                request: SimRequest::Deploy(d.clone()),
+            })
+            .collect();
+
+        for index in 0..config.windows {
+            let start = config.window * index as u32;
+            for _ in 0..config.lookups_per_window {
+                // Squaring skews the lookups toward the first deployments
+                let skewed = rng.unit().powi(2);
+                let deployment = &deployments[(skewed * deployments.len() as f64) as usize];
+                let offset = config.window.mul_f64(rng.unit());
+
+                requests.push(TimedRequest {
+                    at: start + offset,
+                    request: SimRequest::Lookup(deployment.id),
+                });
+            }
+        }
+
+        requests.sort_by_key(|r| r.at);
+
+        Self {
+            window: config.window,
+            servers,
+            requests,
+        }
+    }
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            cost_per_request: 2.,
+            scale_out: ScaleOutConfig::default(),
+        }
+    }
+}
+
+impl Default for SyntheticConfig {
+    fn default() -> Self {
+        Self {
+            servers: 4,
+            windows: 120,
+            window: Duration::from_secs(1),
+            deployments: 3,
+            lookups_per_window: 20,
+            seed: 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::scheduler::registry::{names, PolicyInfo};
+
+    use super::*;
+
+    #[test]
+    fn test_synthetic_is_reproducible() {
+        let config = SyntheticConfig::default();
+        let a = Trace::synthetic(&config);
+        let b = Trace::synthetic(&config);
+
+        assert_eq!(a, b);
+        assert_ne!(a, Trace::synthetic(&SyntheticConfig { seed: 2, ..config }));
+    }
+
+    #[test]
+    fn test_requests_land_in_their_window() {
+        let deployment = DeploymentInfo::new("app".to_owned(), "https://example.com".to_owned());
+        let background = ResourceUtilization {
+            cpu: 10,
+            ..Default::default()
+        };
+        let trace = Trace {
+            window: Duration::from_secs(1),
+            servers: vec![vec![background.clone(), background]],
+            requests: vec![
+                TimedRequest {
+                    at: Duration::ZERO,
+                    request: SimRequest::Deploy(deployment.clone()),
+                },
+                TimedRequest {
+                    at: Duration::from_millis(1500),
+                    request: SimRequest::Lookup(deployment.id),
+                },
+            ],
+        };
+
+        let scheduler = PolicyInfo::default().create().unwrap();
+        let report = Simulation::new(scheduler, SimulationConfig::default()).run(&trace);
+
+        // The lookup adds to the second, i.e., the last window
+        assert_eq!(report.failed_lookups, 0);
+        assert_eq!(report.mean_utilization, (10. + 12.) / 2.);
+    }
+
+    #[test]
+    fn test_runs_every_policy() {
+        let config = SyntheticConfig::default();
+        let trace = Trace::synthetic(&config);
+
+        for name in names() {
+            let scheduler = PolicyInfo::new(name).create().unwrap();
+            let report = Simulation::new(scheduler, SimulationConfig::default()).run(&trace);
+
+            assert_eq!(report.deployments, config.deployments);
+            assert_eq!(report.lookups, config.windows * config.lookups_per_window);
+            assert_eq!(report.failed_lookups, 0);
+            assert_eq!(report.failed_placements, 0);
+            assert_eq!(report.windows, config.windows);
+            assert!((0. ..=1.).contains(&report.placement_quality));
+        }
+    }
+}
@@ -1,9 +1,13 @@
+use std::collections::{vec_deque, VecDeque};
+use std::time::Duration;
+
 use prost_types::Timestamp;
+use uuid::Uuid;
 
 use crate::{
-    proto::{MonitorWindow, ResourceUtilization},
+    proto::{DeploymentLatency, DeploymentQueue, MonitorWindow, ResourceUtilization},
     utils::{subtract_window, IdMap},
-    ServerInfo,
+    Error, Result, ServerInfo,
 };
 
 pub type StatsMap = IdMap<ServerStats>;
@@ -11,31 +15,227 @@ pub type StatsMap = IdMap<ServerStats>;
 #[derive(Clone, Debug)]
 pub struct ServerStats {
     pub server: ServerInfo,
-    pub stats: Vec<MonitorWindow>,
+    /// The latest windows up to `config.retention`, from the oldest
+    stats: VecDeque<MonitorWindow>,
+    /// Exponentially weighted moving average over every window ever appended
+    average: Option<Utilization>,
+    config: StatsConfig,
+    /// The latest observed latency of each deployment on the server
+    pub latencies: IdMap<LatencyStats>,
+    /// The latest admission queue of each deployment on the server
+    pub queues: IdMap<QueueStats>,
+}
+
+/// StatsConfig configures how long `ServerStats` remembers the reported windows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatsConfig {
+    /// Number of windows to keep
+    pub retention: usize,
+    /// Time after which a window weighs half in the moving averages
+    pub half_life: Duration,
+}
+
+/// Utilization is the moving average of each resource, in the units of `ResourceUtilization`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Utilization {
+    pub cpu: f64,
+    pub ram_used: f64,
+    pub gpu: f64,
+    pub vram_used: f64,
+}
+
+pub const DEFAULT_RETENTION: usize = 64;
+pub const DEFAULT_HALF_LIFE: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct QueueStats {
+    pub deployment_id: Uuid,
+    pub in_flight: u32,
+    pub queued: u32,
+    /// Requests rejected in the last report period
+    pub rejected: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct LatencyStats {
+    pub deployment_id: Uuid,
+    pub p95: Duration,
+    pub count: u32,
 }
 
 impl ServerStats {
     pub fn new(server: ServerInfo) -> Self {
-        let stats = Vec::new();
-        Self { server, stats }
+        Self::with_config(server, StatsConfig::default())
+    }
+
+    pub fn with_config(server: ServerInfo, config: StatsConfig) -> Self {
+        Self {
+            server,
+            stats: VecDeque::new(),
+            average: None,
+            config,
+            latencies: IdMap::new(),
+            queues: IdMap::new(),
+        }
     }
 
     pub fn from_stats(server: ServerInfo, stats: Vec<MonitorWindow>) -> Self {
-        Self { server, stats }
+        let mut this = Self::new(server);
+        this.append(stats);
+        this
+    }
+
+    /// latest returns the utilization in the last reported window.
+    pub fn latest(&self) -> Option<&ResourceUtilization> {
+        self.stats.back()?.utilization.as_ref()
+    }
+
+    /// average returns the moving average of the utilization, where recent windows weigh more.
+    pub fn average(&self) -> Option<&Utilization> {
+        self.average.as_ref()
+    }
+
+    /// len returns the number of the retained windows.
+    pub fn len(&self) -> usize {
+        self.stats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    /// configure applies `config`, dropping the windows beyond the new retention.
+    pub fn configure(&mut self, config: StatsConfig) {
+        self.config = config;
+        self.truncate();
+    }
+
+    pub fn p95_latency(&self, deployment_id: &Uuid) -> Option<Duration> {
+        self.latencies.0.get(deployment_id).map(|l| l.p95)
     }
 
+    /// update_latencies overwrites the latencies of the deployments that have been requested.
+    /// Others keep their last known values.
+    pub fn update_latencies(&mut self, latencies: Vec<LatencyStats>) {
+        for latency in latencies {
+            if latency.count > 0 {
+                self.latencies.0.insert(latency.deployment_id, latency);
+            }
+        }
+    }
+
+    /// windows iterates over the retained windows, from the oldest.
     pub fn windows(&self) -> Windows {
         let inner = self.stats.iter();
         Windows { inner }
     }
 
-    pub fn append(&mut self, mut window: Vec<MonitorWindow>) {
-        self.stats.append(&mut window)
+    pub fn append(&mut self, windows: Vec<MonitorWindow>) {
+        for window in windows {
+            if let Some(utilization) = &window.utilization {
+                let weight = self.config.weight_of(&window);
+                let current = Utilization::from(utilization);
+
+                self.average = Some(match self.average {
+                    Some(average) => average.decayed(&current, weight),
+                    None => current,
+                });
+            }
+
+            self.stats.push_back(window);
+        }
+
+        self.truncate();
+    }
+
+    pub fn queue(&self, deployment_id: &Uuid) -> Option<&QueueStats> {
+        self.queues.0.get(deployment_id)
+    }
+
+    /// is_saturated returns whether the server rejected requests to the deployment recently.
+    pub fn is_saturated(&self, deployment_id: &Uuid) -> bool {
+        self.queue(deployment_id).is_some_and(|q| q.rejected > 0)
+    }
+
+    /// update_queues overwrites the queues of the reported deployments.
+    pub fn update_queues(&mut self, queues: Vec<QueueStats>) {
+        for queue in queues {
+            self.queues.0.insert(queue.deployment_id, queue);
+        }
+    }
+
+    /// merge appends the windows, the latencies and the queues of `other`,
+    /// reported by the same server.
+    pub fn merge(&mut self, other: ServerStats) {
+        self.append(other.stats.into());
+        self.update_latencies(other.latencies.0.into_values().collect());
+        self.update_queues(other.queues.0.into_values().collect());
+    }
+
+    fn truncate(&mut self) {
+        while self.stats.len() > self.config.retention {
+            self.stats.pop_front();
+        }
+    }
+}
+
+impl StatsConfig {
+    /// weight_of returns the weight of `window` in the moving averages, by its length.
+    fn weight_of(&self, window: &MonitorWindow) -> f64 {
+        let length = window_length(window).unwrap_or(Duration::from_secs(1));
+        let half_lives = length.as_secs_f64() / self.half_life.as_secs_f64().max(f64::EPSILON);
+
+        1. - 0.5_f64.powf(half_lives)
+    }
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            retention: DEFAULT_RETENTION,
+            half_life: DEFAULT_HALF_LIFE,
+        }
+    }
+}
+
+impl Utilization {
+    /// decayed moves the average toward `current` by `weight`.
+    fn decayed(&self, current: &Self, weight: f64) -> Self {
+        let mix = |old: f64, new: f64| old + (new - old) * weight;
+
+        Self {
+            cpu: mix(self.cpu, current.cpu),
+            ram_used: mix(self.ram_used, current.ram_used),
+            gpu: mix(self.gpu, current.gpu),
+            vram_used: mix(self.vram_used, current.vram_used),
+        }
     }
 }
 
+impl From<&ResourceUtilization> for Utilization {
+    fn from(utilization: &ResourceUtilization) -> Self {
+        Self {
+            cpu: utilization.cpu as _,
+            ram_used: utilization.ram_used as _,
+            gpu: utilization.gpu as _,
+            vram_used: utilization.vram_used as _,
+        }
+    }
+}
+
+/// window_length returns the length of the window, if it is reported and positive.
+fn window_length(window: &MonitorWindow) -> Option<Duration> {
+    let window = window.window.as_ref()?;
+    let (start, end) = (window.start.as_ref()?, window.end.as_ref()?);
+
+    let nanos =
+        (end.seconds - start.seconds) as i128 * 1_000_000_000 + (end.nanos - start.nanos) as i128;
+
+    (nanos > 0).then(|| Duration::from_nanos(nanos as _))
+}
+
 pub struct Windows<'a> {
-    inner: std::slice::Iter<'a, MonitorWindow>,
+    inner: vec_deque::Iter<'a, MonitorWindow>,
 }
 
 pub struct Window {
@@ -67,3 +267,144 @@ impl<'a> Iterator for Windows<'a> {
         })
     }
 }
+
+/// unsaturated returns the stats of the servers that have not rejected requests
+/// to the deployment recently.
+pub fn unsaturated(deployment_id: &Uuid, stats_map: &StatsMap) -> StatsMap {
+    let map = stats_map
+        .iter()
+        .filter(|(_, stats)| !stats.is_saturated(deployment_id))
+        .map(|(id, stats)| (*id, stats.clone()))
+        .collect();
+
+    IdMap(map)
+}
+
+/// least_utilized returns the server with the lowest average CPU utilization.
+/// Servers that have not reported any window yet are regarded as idle.
+/// Unlike the policies, this is deterministic and keeps no state, e.g., a round-robin cursor.
+pub fn least_utilized(stats_map: &StatsMap) -> Option<ServerInfo> {
+    stats_map
+        .iter()
+        .map(|(id, stats)| (stats.average().map_or(0., |u| u.cpu), id, stats))
+        .min_by(|(a, x, _), (b, y, _)| a.total_cmp(b).then(x.cmp(y)))
+        .map(|(_, _, stats)| stats.server.clone())
+}
+
+impl TryFrom<DeploymentQueue> for QueueStats {
+    type Error = Error;
+    fn try_from(queue: DeploymentQueue) -> Result<Self> {
+        Ok(Self {
+            deployment_id: Uuid::parse_str(&queue.deployment_id)?,
+            in_flight: queue.in_flight,
+            queued: queue.queued,
+            rejected: queue.rejected,
+        })
+    }
+}
+
+impl TryFrom<DeploymentLatency> for LatencyStats {
+    type Error = Error;
+    fn try_from(latency: DeploymentLatency) -> Result<Self> {
+        Ok(Self {
+            deployment_id: Uuid::parse_str(&latency.deployment_id)?,
+            p95: Duration::from_micros(latency.p95_micros),
+            count: latency.count,
+        })
+    }
+}
+
+/// Stats of servers for the tests of the policies
+#[cfg(test)]
+pub mod fixture {
+    use uuid::Uuid;
+
+    use crate::proto::{MonitorWindow, ResourceUtilization};
+    use crate::utils::IdMap;
+    use crate::ServerInfo;
+
+    use super::{ServerStats, StatsMap};
+
+    pub fn server() -> ServerInfo {
+        ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4())
+    }
+
+    /// window returns a window of `cpu` utilization, without its time span.
+    pub fn window(cpu: i32) -> MonitorWindow {
+        MonitorWindow {
+            window: None,
+            utilization: Some(ResourceUtilization {
+                cpu,
+                ..Default::default()
+            }),
+        }
+    }
+
+    /// stats_with returns the stats of `server` that has reported a window of `utilization`.
+    pub fn stats_with(server: ServerInfo, utilization: ResourceUtilization) -> ServerStats {
+        let window = MonitorWindow {
+            utilization: Some(utilization),
+            ..window(0)
+        };
+
+        ServerStats::from_stats(server, vec![window])
+    }
+
+    pub fn stats_with_cpu(cpu: i32) -> ServerStats {
+        ServerStats::from_stats(server(), vec![window(cpu)])
+    }
+
+    pub fn map(stats: Vec<ServerStats>) -> StatsMap {
+        IdMap(stats.into_iter().map(|s| (s.server.id, s)).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::fixture::window;
+    use super::*;
+
+    fn stats(config: StatsConfig) -> ServerStats {
+        ServerStats::with_config(fixture::server(), config)
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut stats = stats(StatsConfig {
+            retention: 3,
+            ..Default::default()
+        });
+
+        stats.append((0..10).map(window).collect());
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats.latest().unwrap().cpu, 9);
+    }
+
+    #[test]
+    fn test_average_decays() {
+        // Windows without time span are one second long
+        let mut stats = stats(StatsConfig {
+            half_life: Duration::from_secs(1),
+            ..Default::default()
+        });
+
+        stats.append(vec![window(0)]);
+        assert_eq!(stats.average().unwrap().cpu, 0.);
+
+        stats.append(vec![window(100)]);
+        assert_eq!(stats.average().unwrap().cpu, 50.);
+
+        stats.append(vec![window(100)]);
+        assert_eq!(stats.average().unwrap().cpu, 75.);
+    }
+
+    #[test]
+    fn test_least_utilized() {
+        let idle = fixture::stats_with_cpu(10);
+        let map = fixture::map(vec![fixture::stats_with_cpu(80), idle.clone()]);
+
+        assert_eq!(least_utilized(&map).unwrap().id, idle.server.id);
+        assert!(least_utilized(&IdMap::new()).is_none());
+    }
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use bytes::Buf;
+use prost::Message;
+use tracing::warn;
+use uuid::Uuid;
+
+use crate::proto::{
+    state_log_entry::Op, Deployment, InstanceLocation, Server, StateLogEntry, StateSnapshot,
+};
+use crate::{CatalogEntryInfo, DeploymentInfo, Result, ServerInfo};
+
+use super::Cluster;
+
+/// Format version of the state files. Files of other versions are not loaded.
+const FORMAT_VERSION: u32 = 1;
+/// Number of log entries after which the log is compacted into a snapshot
+const COMPACT_AFTER: usize = 128;
+
+const STORE_DIR_NAME: &'static str = "scheduler";
+const SNAPSHOT_FILE_NAME: &'static str = "state.pb";
+const LOG_FILE_NAME: &'static str = "state.wal";
+
+/// StateOp is a change to the cluster that is persisted before being applied.
+#[derive(Clone, Debug)]
+pub enum StateOp {
+    Join(ServerInfo),
+    Leave(Uuid),
+    PutDeployment(CatalogEntryInfo),
+    RemoveDeployment(Uuid),
+    AddInstance(DeploymentInfo, ServerInfo),
+    /// Deployment id and server id
+    RemoveInstance(Uuid, Uuid),
+}
+
+/// StateStore persists the scheduler's view of the cluster under the database root.
+/// It consists of a snapshot of `ClusterState` and a write-ahead log of `StateOp`s.
+#[derive(Clone, Debug)]
+pub struct StateStore {
+    dir: PathBuf,
+    inner: Arc<Mutex<StoreInner>>,
+}
+
+#[derive(Debug)]
+struct StoreInner {
+    sequence: u64,
+    appended: usize,
+}
+
+impl StateStore {
+    pub fn open(root: &PathBuf) -> Result<Self> {
+        let dir = root.join(STORE_DIR_NAME);
+        fs::create_dir_all(&dir)?;
+
+        let inner = Arc::new(Mutex::new(StoreInner {
+            sequence: 0,
+            appended: 0,
+        }));
+
+        Ok(Self { dir, inner })
+    }
+
+    /// load reads the snapshot and replays the log on top of it.
+    /// Returns `None` if no state has been saved.
+    pub fn load(&self) -> Result<Option<Cluster>> {
+        let buf = match fs::read(self.snapshot_path()) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let snapshot = StateSnapshot::decode(&buf[..])
+            .map_err(|e| format!("failed to decode state snapshot: {e}"))?;
+
+        if snapshot.version != FORMAT_VERSION {
+            Err(format!(
+                "unsupported state format version: {}",
+                snapshot.version
+            ))?;
+        }
+
+        let mut cluster: Cluster = snapshot
+            .cluster
+            .ok_or("Cluster in snapshot cannot be empty".to_owned())?
+            .try_into()?;
+        let mut sequence = snapshot.sequence;
+
+        let log = match fs::read(self.log_path()) {
+            Ok(log) => log,
+            Err(e) if e.kind() == ErrorKind::NotFound => vec![],
+            Err(e) => return Err(e.into()),
+        };
+        let mut buf = &log[..];
+
+        while buf.has_remaining() {
+            let entry = match StateLogEntry::decode_length_delimited(&mut buf) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // The last entry may have been written partially
+                    warn!("ignoring the rest of the state log: {e}");
+                    break;
+                }
+            };
+
+            // Entries up to the snapshot's sequence are already included
+            if entry.sequence <= sequence {
+                continue;
+            }
+
+            let op = entry
+                .op
+                .ok_or("Op in log entry cannot be empty".to_owned())?;
+            cluster.apply(op.try_into()?);
+            sequence = entry.sequence;
+        }
+
+        self.lock().sequence = sequence;
+
+        Ok(Some(cluster))
+    }
+
+    pub fn append(&self, op: &StateOp) -> Result<()> {
+        let mut inner = self.lock();
+
+        let entry = StateLogEntry {
+            sequence: inner.sequence + 1,
+            op: Some(op.clone().into()),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log_path())?;
+        file.write_all(&entry.encode_length_delimited_to_vec())?;
+        file.sync_data()?;
+
+        inner.sequence = entry.sequence;
+        inner.appended += 1;
+
+        Ok(())
+    }
+
+    pub fn needs_compaction(&self) -> bool {
+        self.lock().appended >= COMPACT_AFTER
+    }
+
+    /// snapshot writes the whole cluster, and truncates the log.
+    pub fn snapshot(&self, cluster: &Cluster) -> Result<()> {
+        let mut inner = self.lock();
+
+        let snapshot = StateSnapshot {
+            version: FORMAT_VERSION,
+            sequence: inner.sequence,
+            cluster: Some(cluster.clone().into()),
+        };
+
+        // Write to a temporary file first, so that a crash does not leave a broken snapshot
+        let tmp_path = self.dir.join(format!("{SNAPSHOT_FILE_NAME}.tmp"));
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&snapshot.encode_to_vec())?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, self.snapshot_path())?;
+
+        File::create(self.log_path())?.sync_all()?;
+        inner.appended = 0;
+
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StoreInner> {
+        self.inner.lock().expect("state store lock is poisoned")
+    }
+
+    fn snapshot_path(&self) -> PathBuf {
+        self.dir.join(SNAPSHOT_FILE_NAME)
+    }
+
+    fn log_path(&self) -> PathBuf {
+        self.dir.join(LOG_FILE_NAME)
+    }
+}
+
+impl Into<Op> for StateOp {
+    fn into(self) -> Op {
+        use StateOp::*;
+
+        match self {
+            Join(server) => Op::Join(server.into()),
+            Leave(id) => Op::Leave(id.to_string()),
+            PutDeployment(entry) => Op::PutDeployment(entry.into()),
+            RemoveDeployment(id) => Op::RemoveDeployment(id.to_string()),
+            AddInstance(deployment, server) => Op::AddInstance(InstanceLocation {
+                deployment: Some(deployment.into()),
+                server: Some(server.into()),
+            }),
+            RemoveInstance(deployment_id, server_id) => Op::RemoveInstance(InstanceLocation {
+                deployment: Some(Deployment {
+                    id: deployment_id.to_string(),
+                    ..Default::default()
+                }),
+                server: Some(Server {
+                    id: server_id.to_string(),
+                    ..Default::default()
+                }),
+            }),
+        }
+    }
+}
+
+impl TryFrom<Op> for StateOp {
+    type Error = crate::Error;
+    fn try_from(op: Op) -> Result<Self> {
+        let location = |l: InstanceLocation| -> Result<(Deployment, Server)> {
+            let deployment = l
+                .deployment
+                .ok_or("Deployment cannot be empty".to_owned())?;
+            let server = l.server.ok_or("Server cannot be empty".to_owned())?;
+            Ok((deployment, server))
+        };
+
+        let op = match op {
+            Op::Join(server) => Self::Join(server.try_into()?),
+            Op::Leave(id) => Self::Leave(Uuid::parse_str(&id)?),
+            Op::PutDeployment(entry) => Self::PutDeployment(entry.try_into()?),
+            Op::RemoveDeployment(id) => Self::RemoveDeployment(Uuid::parse_str(&id)?),
+            Op::AddInstance(l) => {
+                let (deployment, server) = location(l)?;
+                Self::AddInstance(deployment.try_into()?, server.try_into()?)
+            }
+            Op::RemoveInstance(l) => {
+                let (deployment, server) = location(l)?;
+                Self::RemoveInstance(
+                    Uuid::parse_str(&deployment.id)?,
+                    Uuid::parse_str(&server.id)?,
+                )
+            }
+        };
+
+        Ok(op)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// store opens a store in a directory that is removed when the returned `TempDir` is dropped.
+    fn store() -> (tempfile::TempDir, StateStore) {
+        let root = tempfile::tempdir().unwrap();
+        let store = StateStore::open(&root.path().to_owned()).unwrap();
+        (root, store)
+    }
+
+    #[test]
+    fn test_load_replays_log() {
+        let (_root, store) = store();
+        assert!(store.load().unwrap().is_none());
+
+        let scheduler = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let server = ServerInfo::with_id("127.0.0.1:50052", Uuid::new_v4());
+        let deployment = DeploymentInfo::new("test".to_owned(), "".to_owned());
+
+        store.snapshot(&Cluster::new(&scheduler)).unwrap();
+
+        store.append(&StateOp::Join(server.clone())).unwrap();
+        let entry = CatalogEntryInfo::new(deployment.clone(), vec![]);
+        store.append(&StateOp::PutDeployment(entry)).unwrap();
+        store
+            .append(&StateOp::AddInstance(deployment.clone(), server.clone()))
+            .unwrap();
+
+        let cluster = store.load().unwrap().unwrap();
+        assert_eq!(cluster.servers.len(), 2);
+        assert_eq!(cluster.instance_count(&deployment.id), 1);
+        assert_eq!(cluster.catalog.0[&deployment.id].desired_replicas, 1);
+    }
+
+    #[test]
+    fn test_load_ignores_partial_entry() {
+        let (_root, store) = store();
+
+        let scheduler = ServerInfo::with_id("127.0.0.1:50051", Uuid::new_v4());
+        let server = ServerInfo::with_id("127.0.0.1:50052", Uuid::new_v4());
+
+        store.snapshot(&Cluster::new(&scheduler)).unwrap();
+        store.append(&StateOp::Join(server)).unwrap();
+
+        // Simulate a crash while writing an entry
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(store.log_path())
+            .unwrap();
+        file.write_all(&[0x20, 0x08]).unwrap();
+
+        let cluster = store.load().unwrap().unwrap();
+        assert_eq!(cluster.servers.len(), 2);
+    }
+}
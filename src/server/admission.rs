@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tonic::body::BoxBody;
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::metadata::MetadataValue;
+use tonic::server::NamedService;
+use tonic::Status;
+use uuid::Uuid;
+
+use crate::proto::DeploymentQueue;
+use crate::utils::{take_ready, IdMap};
+
+/// Trailer that tells gRPC clients how long to wait before retrying
+const RETRY_PUSHBACK_KEY: &str = "grpc-retry-pushback-ms";
+
+/// AdmissionControl limits the concurrent requests to each deployment on this server.
+/// Requests beyond the limit wait in a bounded queue, and are rejected with
+/// `RESOURCE_EXHAUSTED` when the queue is full or they have waited too long.
+#[derive(Clone, Debug)]
+pub struct AdmissionControl {
+    config: AdmissionConfig,
+    gates: Arc<Mutex<IdMap<Arc<Gate>>>>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AdmissionConfig {
+    /// Requests processed at the same time
+    pub max_in_flight: usize,
+    /// Requests waiting to be processed
+    pub max_queue: usize,
+    /// How long a request may wait in the queue
+    pub queue_timeout: Duration,
+}
+
+#[derive(Debug)]
+struct Gate {
+    permits: Arc<Semaphore>,
+    queued: AtomicU32,
+    /// Rejected since the last report
+    rejected: AtomicU32,
+}
+
+/// Admitted wraps an application service, and applies the admission control to its requests.
+#[derive(Clone, Debug)]
+pub struct Admitted<S> {
+    inner: S,
+    gate: Arc<Gate>,
+    config: AdmissionConfig,
+}
+
+impl AdmissionControl {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            config,
+            gates: Arc::new(Mutex::new(IdMap::new())),
+        }
+    }
+
+    pub fn wrap<S>(&self, deployment_id: Uuid, inner: S) -> Admitted<S> {
+        let gate = self
+            .lock()
+            .0
+            .entry(deployment_id)
+            .or_insert_with(|| Arc::new(Gate::new(self.config.max_in_flight)))
+            .clone();
+
+        Admitted {
+            inner,
+            gate,
+            config: self.config.clone(),
+        }
+    }
+
+    /// drain returns the queue of each deployment, and resets the rejected counts.
+    pub fn drain(&self) -> Vec<DeploymentQueue> {
+        let max_in_flight = self.config.max_in_flight;
+
+        self.lock()
+            .iter()
+            .map(|(id, gate)| DeploymentQueue {
+                deployment_id: id.to_string(),
+                in_flight: (max_in_flight - gate.permits.available_permits()) as _,
+                queued: gate.queued.load(Ordering::Relaxed),
+                rejected: gate.rejected.swap(0, Ordering::Relaxed),
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IdMap<Arc<Gate>>> {
+        self.gates
+            .lock()
+            .expect("admission control lock is poisoned")
+    }
+}
+
+impl Gate {
+    fn new(max_in_flight: usize) -> Self {
+        Self {
+            permits: Arc::new(Semaphore::new(max_in_flight)),
+            queued: AtomicU32::new(0),
+            rejected: AtomicU32::new(0),
+        }
+    }
+
+    async fn admit(&self, config: &AdmissionConfig) -> Result<OwnedSemaphorePermit, Status> {
+        if let Ok(permit) = self.permits.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+
+        let queued = self.queued.fetch_add(1, Ordering::Relaxed);
+        let result = if queued as usize >= config.max_queue {
+            Err("queue is full")
+        } else {
+            tokio::time::timeout(config.queue_timeout, self.permits.clone().acquire_owned())
+                .await
+                .map_err(|_| "queued for too long")
+                .and_then(|p| p.map_err(|_| "deployment is closed"))
+        };
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+
+        result.map_err(|reason| {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            let waiting = self.queued.load(Ordering::Relaxed) as usize;
+            reject(reason, retry_after(waiting, config))
+        })
+    }
+}
+
+/// retry_after scales the queue timeout by the depth of the queue, including the rejected
+/// request, as a deeper queue takes longer to drain.
+fn retry_after(waiting: usize, config: &AdmissionConfig) -> Duration {
+    let capacity = config.max_queue.max(1);
+    let depth = (waiting + 1).min(capacity);
+    config.queue_timeout.mul_f64(depth as f64 / capacity as f64)
+}
+
+/// reject creates the status of a rejected request, with the time to wait before retrying.
+fn reject(reason: &str, retry_after: Duration) -> Status {
+    let mut status = Status::resource_exhausted(format!("Request rejected: {reason}"));
+
+    let millis = MetadataValue::from(retry_after.as_millis() as u64);
+    status.metadata_mut().insert(RETRY_PUSHBACK_KEY, millis);
+
+    status
+}
+
+impl<S, B> Service<http::Request<B>> for Admitted<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let mut inner = take_ready(&mut self.inner);
+
+        let gate = self.gate.clone();
+        let config = self.config.clone();
+
+        Box::pin(async move {
+            let _permit = match gate.admit(&config).await {
+                Ok(permit) => permit,
+                Err(status) => return Ok(status.to_http()),
+            };
+
+            inner.call(request).await
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for Admitted<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: 4,
+            max_queue: 16,
+            queue_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config() -> AdmissionConfig {
+        AdmissionConfig {
+            max_in_flight: 1,
+            max_queue: 1,
+            queue_timeout: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejects_when_queue_is_full() {
+        let gate = Arc::new(Gate::new(1));
+        let config = config();
+
+        let _running = gate.admit(&config).await.unwrap();
+
+        // The second one waits in the queue, and the third one is rejected
+        let waiting = tokio::spawn({
+            let gate = gate.clone();
+            let config = config.clone();
+            async move { gate.admit(&config).await.map(|_| ()) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        let status = gate.admit(&config).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert!(status.metadata().get(RETRY_PUSHBACK_KEY).is_some());
+
+        // The waiting one times out
+        assert!(waiting.await.unwrap().is_err());
+        assert_eq!(gate.rejected.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_retry_after_follows_queue_depth() {
+        let config = AdmissionConfig {
+            max_queue: 4,
+            queue_timeout: Duration::from_millis(400),
+            ..config()
+        };
+
+        assert_eq!(retry_after(0, &config), Duration::from_millis(100));
+        assert_eq!(retry_after(2, &config), Duration::from_millis(300));
+        assert_eq!(retry_after(4, &config), Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn test_admits_after_release() {
+        let gate = Gate::new(1);
+        let config = config();
+
+        let running = gate.admit(&config).await.unwrap();
+        drop(running);
+
+        assert!(gate.admit(&config).await.is_ok());
+    }
+}
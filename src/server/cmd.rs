@@ -1,5 +1,7 @@
 use clap::{Args, Subcommand};
 
+use crate::{node::parse_label, scheduler::registry::DEFAULT_SCHEDULER, telemetry::LogFormat};
+
 #[derive(Clone, Subcommand)]
 pub enum ServerCommand {
     Start(StartCommand),
@@ -15,4 +17,77 @@ pub struct StartCommand {
 
     #[arg(short = 'i', long = "id")]
     pub id: Option<String>,
+
+    /// Label of this server in the form of key=value, used for deployment affinity
+    #[arg(long = "label", value_parser = parse_label)]
+    pub labels: Vec<(String, String)>,
+
+    /// Scheduling policy of the cluster. Ignored when joining an existing cluster.
+    #[arg(long = "scheduler", default_value = DEFAULT_SCHEDULER)]
+    pub scheduler: String,
+
+    /// CPU utilization in percent above which deployments are scaled out
+    #[arg(long = "scale-out-threshold")]
+    pub scale_out_threshold: Option<usize>,
+
+    /// CPU utilization in percent below which deployments are scaled in
+    #[arg(long = "scale-in-threshold")]
+    pub scale_in_threshold: Option<usize>,
+
+    /// Time in milliseconds between the checks for deployments to scale in
+    #[arg(long = "scale-in-interval-ms")]
+    pub scale_in_interval_ms: Option<u64>,
+
+    /// Time in milliseconds that a deployment must stay underutilized before it is scaled in
+    #[arg(long = "scale-in-window-ms")]
+    pub scale_in_window_ms: Option<u64>,
+
+    /// Number of instances of a deployment, above which it is not scaled out
+    #[arg(long = "max-replicas", value_parser = parse_positive)]
+    pub max_replicas: Option<usize>,
+
+    /// Number of monitor windows that the scheduler keeps for each server
+    #[arg(long = "stats-retention", value_parser = parse_positive)]
+    pub stats_retention: Option<usize>,
+
+    /// Time in milliseconds after which a monitor window weighs half in the averages
+    #[arg(long = "stats-half-life-ms")]
+    pub stats_half_life_ms: Option<u64>,
+
+    /// Requests processed at the same time by each deployment on this server
+    #[arg(long = "max-in-flight")]
+    pub max_in_flight: Option<usize>,
+
+    /// Requests waiting for each deployment on this server, beyond which they are rejected
+    #[arg(long = "max-queue")]
+    pub max_queue: Option<usize>,
+
+    /// Time in milliseconds that a request may wait before it is rejected
+    #[arg(long = "queue-timeout-ms")]
+    pub queue_timeout_ms: Option<u64>,
+
+    /// Log filter, e.g., "info" or "info,laqista::scheduler=debug". Overridden by RUST_LOG.
+    #[arg(long = "log-level", default_value = "info")]
+    pub log_level: String,
+
+    #[arg(long = "log-format", value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Address of the HTTP endpoint that serves Prometheus metrics at /metrics.
+    /// Defaults to the listen address with the port plus 1000, e.g., 127.0.0.1:51051
+    #[arg(long = "metrics-listen")]
+    pub metrics_listen: Option<String>,
+}
+
+/// parse_positive parses a number given on the command line, which must be at least 1.
+fn parse_positive(s: &str) -> std::result::Result<usize, String> {
+    let n: usize = s
+        .parse()
+        .map_err(|e| format!("invalid number {s:?}: {e}"))?;
+
+    if n == 0 {
+        return Err("must be at least 1".to_owned());
+    }
+
+    Ok(n)
 }
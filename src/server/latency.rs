@@ -0,0 +1,168 @@
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::server::NamedService;
+use uuid::Uuid;
+
+use crate::proto::DeploymentLatency;
+use crate::utils::{random_below, IdMap};
+
+/// Samples kept for each deployment between reports. Beyond this, a uniform sample of the
+/// requests is kept, so that memory stays bounded while the reporter cannot reach the scheduler.
+const MAX_SAMPLES: usize = 1024;
+
+/// LatencyRecorder collects the response time of requests to each deployment on this server,
+/// until the reporter drains them.
+#[derive(Clone, Debug)]
+pub struct LatencyRecorder {
+    samples: Arc<Mutex<IdMap<Samples>>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Samples {
+    /// Requests recorded, including those not kept in `latencies`
+    count: usize,
+    latencies: Vec<Duration>,
+}
+
+/// Timed wraps an application service, and records the latency of its requests.
+#[derive(Clone, Debug)]
+pub struct Timed<S> {
+    inner: S,
+    deployment_id: Uuid,
+    recorder: LatencyRecorder,
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self {
+            samples: Arc::new(Mutex::new(IdMap::new())),
+        }
+    }
+
+    pub fn record(&self, deployment_id: Uuid, latency: Duration) {
+        let mut samples = self.lock();
+        let samples = samples.0.entry(deployment_id).or_default();
+        samples.count += 1;
+
+        // Reservoir sampling keeps each request with the same probability
+        if samples.latencies.len() < MAX_SAMPLES {
+            samples.latencies.push(latency);
+        } else {
+            let i = random_below(samples.count);
+            if i < MAX_SAMPLES {
+                samples.latencies[i] = latency;
+            }
+        }
+    }
+
+    /// drain returns the p95 latency of each deployment, and clears the samples.
+    pub fn drain(&self) -> Vec<DeploymentLatency> {
+        let samples = std::mem::take(&mut self.lock().0);
+
+        samples
+            .into_iter()
+            .filter(|(_, s)| !s.latencies.is_empty())
+            .map(|(id, mut s)| {
+                s.latencies.sort();
+                DeploymentLatency {
+                    deployment_id: id.to_string(),
+                    count: s.count as _,
+                    p95_micros: percentile(&s.latencies, 95).as_micros() as _,
+                }
+            })
+            .collect()
+    }
+
+    pub fn wrap<S>(&self, deployment_id: Uuid, inner: S) -> Timed<S> {
+        Timed {
+            inner,
+            deployment_id,
+            recorder: self.clone(),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, IdMap<Samples>> {
+        self.samples
+            .lock()
+            .expect("latency recorder lock is poisoned")
+    }
+}
+
+/// percentile returns the `p`-th percentile of non-empty, sorted samples.
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (sorted.len() * p).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+impl<S, B> Service<http::Request<B>> for Timed<S>
+where
+    S: Service<http::Request<B>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let start = Instant::now();
+        let future = self.inner.call(request);
+
+        let deployment_id = self.deployment_id;
+        let recorder = self.recorder.clone();
+
+        Box::pin(async move {
+            let response = future.await;
+            recorder.record(deployment_id, start.elapsed());
+            response
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for Timed<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drain_p95() {
+        let recorder = LatencyRecorder::new();
+        let id = Uuid::new_v4();
+
+        for millis in 1..=100 {
+            recorder.record(id, Duration::from_millis(millis));
+        }
+
+        let latencies = recorder.drain();
+        assert_eq!(latencies.len(), 1);
+        assert_eq!(latencies[0].count, 100);
+        assert_eq!(latencies[0].p95_micros, 95_000);
+
+        assert!(recorder.drain().is_empty());
+    }
+
+    #[test]
+    fn test_samples_are_bounded() {
+        let recorder = LatencyRecorder::new();
+        let id = Uuid::new_v4();
+
+        for _ in 0..MAX_SAMPLES * 4 {
+            recorder.record(id, Duration::from_millis(10));
+        }
+
+        assert_eq!(recorder.lock().0[&id].latencies.len(), MAX_SAMPLES);
+
+        let latencies = recorder.drain();
+        assert_eq!(latencies[0].count, (MAX_SAMPLES * 4) as u32);
+        assert_eq!(latencies[0].p95_micros, 10_000);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::http::{header::CONTENT_TYPE, HeaderName};
+use axum::routing::get;
+use axum::Router;
+use tokio_util::sync::CancellationToken;
+use tonic::body::BoxBody;
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::server::NamedService;
+use uuid::Uuid;
+
+use crate::proto::{ClusterState, ResourceUtilization};
+use crate::utils::IdMap;
+
+/// Upper bounds of the RPC latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 9] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1., 5.];
+
+const CONTENT_TYPE_TEXT: &str = "text/plain; version=0.0.4";
+
+/// Metrics collects the state of this daemon, and exposes it in the Prometheus text format.
+#[derive(Clone, Debug)]
+pub struct Metrics {
+    state: Arc<Mutex<MetricsState>>,
+}
+
+#[derive(Debug, Default)]
+struct MetricsState {
+    /// From the latest cluster state known to this server
+    cluster: Option<ClusterMetrics>,
+    /// Keyed by the path, e.g., "/laqista.Scheduler/Lookup"
+    rpcs: BTreeMap<String, RpcMetrics>,
+    scale_outs: BTreeMap<Uuid, u64>,
+    report_failures: u64,
+    utilization: Option<ResourceUtilization>,
+}
+
+#[derive(Debug)]
+struct ClusterMetrics {
+    group: u32,
+    servers: usize,
+    /// Name and number of instances of each deployment
+    instances: IdMap<(String, usize)>,
+}
+
+#[derive(Debug, Default)]
+struct RpcMetrics {
+    count: u64,
+    errors: u64,
+    /// Cumulative counts for `LATENCY_BUCKETS`
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+}
+
+/// Measured wraps a gRPC service, and counts its requests and their latencies.
+#[derive(Clone, Debug)]
+pub struct Measured<S> {
+    inner: S,
+    metrics: Metrics,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(MetricsState::default())),
+        }
+    }
+
+    pub fn wrap<S>(&self, inner: S) -> Measured<S> {
+        Measured {
+            inner,
+            metrics: self.clone(),
+        }
+    }
+
+    pub fn observe_cluster(&self, cluster: &ClusterState) {
+        let instances = cluster
+            .instances
+            .iter()
+            .filter_map(|i| {
+                let deployment = i.deployment.as_ref()?;
+                let id = Uuid::parse_str(&deployment.id).ok()?;
+                Some((id, (deployment.name.clone(), i.locations.len())))
+            })
+            .collect();
+
+        self.lock().cluster = Some(ClusterMetrics {
+            group: cluster.group.as_ref().map_or(0, |g| g.number),
+            servers: cluster.servers.len(),
+            instances: IdMap(instances),
+        });
+    }
+
+    pub fn observe_rpc(&self, path: &str, latency: Duration, success: bool) {
+        let mut state = self.lock();
+        let rpc = state.rpcs.entry(path.to_owned()).or_default();
+
+        let seconds = latency.as_secs_f64();
+        rpc.count += 1;
+        rpc.sum += seconds;
+        if !success {
+            rpc.errors += 1;
+        }
+
+        for (bucket, bound) in rpc.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    pub fn observe_scale_out(&self, deployment_id: Uuid) {
+        *self.lock().scale_outs.entry(deployment_id).or_default() += 1;
+    }
+
+    pub fn observe_report_failure(&self) {
+        self.lock().report_failures += 1;
+    }
+
+    pub fn observe_utilization(&self, utilization: &ResourceUtilization) {
+        self.lock().utilization = Some(utilization.clone());
+    }
+
+    /// render formats the metrics in the Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let state = self.lock();
+        let mut out = String::new();
+
+        if let Some(cluster) = &state.cluster {
+            header(
+                &mut out,
+                "laqista_cluster_group",
+                "gauge",
+                "Number of the group",
+            );
+            let _ = writeln!(out, "laqista_cluster_group {}", cluster.group);
+
+            header(
+                &mut out,
+                "laqista_cluster_servers",
+                "gauge",
+                "Servers in the cluster",
+            );
+            let _ = writeln!(out, "laqista_cluster_servers {}", cluster.servers);
+
+            let name = "laqista_deployment_instances";
+            header(&mut out, name, "gauge", "Instances of each deployment");
+            for (id, (deployment_name, count)) in cluster.instances.iter() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{deployment_id=\"{id}\",name=\"{}\"}} {count}",
+                    escape(deployment_name)
+                );
+            }
+        }
+
+        let name = "laqista_rpc_requests_total";
+        header(&mut out, name, "counter", "RPCs handled by this server");
+        for (path, rpc) in &state.rpcs {
+            let _ = writeln!(out, "{name}{{{}}} {}", rpc_labels(path), rpc.count);
+        }
+
+        let name = "laqista_rpc_errors_total";
+        header(&mut out, name, "counter", "RPCs that ended with an error");
+        for (path, rpc) in &state.rpcs {
+            let _ = writeln!(out, "{name}{{{}}} {}", rpc_labels(path), rpc.errors);
+        }
+
+        let name = "laqista_rpc_duration_seconds";
+        header(&mut out, name, "histogram", "Time to handle RPCs");
+        for (path, rpc) in &state.rpcs {
+            let labels = rpc_labels(path);
+            for (count, bound) in rpc.buckets.iter().zip(LATENCY_BUCKETS) {
+                let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{bound}\"}} {count}");
+            }
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"+Inf\"}} {}", rpc.count);
+            let _ = writeln!(out, "{name}_sum{{{labels}}} {}", rpc.sum);
+            let _ = writeln!(out, "{name}_count{{{labels}}} {}", rpc.count);
+        }
+
+        let name = "laqista_scale_outs_total";
+        header(
+            &mut out,
+            name,
+            "counter",
+            "Scale-outs started by this scheduler",
+        );
+        for (id, count) in &state.scale_outs {
+            let _ = writeln!(out, "{name}{{deployment_id=\"{id}\"}} {count}");
+        }
+
+        let name = "laqista_report_failures_total";
+        header(
+            &mut out,
+            name,
+            "counter",
+            "Reports that failed to reach the scheduler",
+        );
+        let _ = writeln!(out, "{name} {}", state.report_failures);
+
+        if let Some(u) = &state.utilization {
+            let name = "laqista_resource_utilization";
+            header(
+                &mut out,
+                name,
+                "gauge",
+                "Latest utilization collected by the monitor",
+            );
+
+            let resources = [
+                ("cpu", u.cpu),
+                ("ram_total", u.ram_total),
+                ("ram_used", u.ram_used),
+                ("gpu", u.gpu),
+                ("vram_total", u.vram_total),
+                ("vram_used", u.vram_used),
+            ];
+            // Negative values are not measured by the monitor
+            for (resource, value) in resources.into_iter().filter(|(_, v)| *v >= 0) {
+                let _ = writeln!(out, "{name}{{resource=\"{resource}\"}} {value}");
+            }
+        }
+
+        out
+    }
+
+    /// bind takes the address of the endpoint before serving, so that a failure can be told apart.
+    pub fn bind(addr: SocketAddr) -> crate::Result<TcpListener> {
+        let listener = TcpListener::bind(addr)
+            .map_err(|e| format!("failed to bind the metrics endpoint to {addr}: {e}"))?;
+        listener
+            .set_nonblocking(true)
+            .map_err(|e| format!("failed to configure the metrics endpoint: {e}"))?;
+
+        Ok(listener)
+    }
+
+    /// serve exposes `/metrics` on `listener` over HTTP until `shutdown` is cancelled.
+    pub async fn serve(
+        self,
+        listener: TcpListener,
+        shutdown: CancellationToken,
+    ) -> crate::Result<()> {
+        let app = Router::new()
+            .route("/metrics", get(scrape))
+            .with_state(self);
+
+        axum::Server::from_tcp(listener)
+            .map_err(|e| format!("failed to serve the metrics endpoint: {e}"))?
+            .serve(app.into_make_service())
+            .with_graceful_shutdown(async move { shutdown.cancelled().await })
+            .await
+            .map_err(|e| format!("metrics endpoint failed: {e}"))?;
+
+        Ok(())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MetricsState> {
+        self.state.lock().expect("metrics lock is poisoned")
+    }
+}
+
+async fn scrape(State(metrics): State<Metrics>) -> ([(HeaderName, &'static str); 1], String) {
+    ([(CONTENT_TYPE, CONTENT_TYPE_TEXT)], metrics.render())
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+/// rpc_labels splits a path like "/laqista.Scheduler/Lookup" into the service and the method.
+fn rpc_labels(path: &str) -> String {
+    let (service, method) = path
+        .trim_start_matches('/')
+        .split_once('/')
+        .unwrap_or(("", path));
+    format!(
+        "service=\"{}\",method=\"{}\"",
+        escape(service),
+        escape(method)
+    )
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl<S, B> Service<http::Request<B>> for Measured<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let start = Instant::now();
+        let path = request.uri().path().to_owned();
+        let future = self.inner.call(request);
+
+        let metrics = self.metrics.clone();
+
+        Box::pin(async move {
+            let response = future.await;
+
+            // Failed RPCs carry the status in the headers, and successful ones in the trailers
+            let success = response.as_ref().is_ok_and(|r| {
+                r.headers()
+                    .get("grpc-status")
+                    .map_or(true, |status| status == "0")
+            });
+            metrics.observe_rpc(&path, start.elapsed(), success);
+
+            response
+        })
+    }
+}
+
+impl<S: NamedService> NamedService for Measured<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::proto::{AppInstanceLocations, Deployment, Group, Server};
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        let deployment_id = Uuid::new_v4();
+
+        metrics.observe_cluster(&ClusterState {
+            group: Some(Group {
+                number: 3,
+                scheduler: None,
+            }),
+            servers: vec![Server::default(), Server::default()],
+            instances: vec![AppInstanceLocations {
+                deployment: Some(Deployment {
+                    id: deployment_id.to_string(),
+                    name: "face".to_owned(),
+                    ..Default::default()
+                }),
+                locations: vec![Server::default()],
+            }],
+            ..Default::default()
+        });
+        metrics.observe_rpc("/laqista.Scheduler/Lookup", Duration::from_millis(20), true);
+        metrics.observe_rpc("/laqista.Scheduler/Lookup", Duration::from_secs(2), false);
+        metrics.observe_utilization(&ResourceUtilization {
+            cpu: 42,
+            ram_used: -1,
+            ..Default::default()
+        });
+
+        let out = metrics.render();
+
+        assert!(out.contains("laqista_cluster_servers 2\n"));
+        assert!(out.contains(&format!(
+            "laqista_deployment_instances{{deployment_id=\"{deployment_id}\",name=\"face\"}} 1\n"
+        )));
+
+        let labels = "service=\"laqista.Scheduler\",method=\"Lookup\"";
+        assert!(out.contains(&format!("laqista_rpc_requests_total{{{labels}}} 2\n")));
+        assert!(out.contains(&format!("laqista_rpc_errors_total{{{labels}}} 1\n")));
+        assert!(out.contains(&format!(
+            "laqista_rpc_duration_seconds_bucket{{{labels},le=\"0.025\"}} 1\n"
+        )));
+
+        assert!(out.contains("laqista_resource_utilization{resource=\"cpu\"} 42\n"));
+        assert!(!out.contains("resource=\"ram_used\""));
+    }
+}
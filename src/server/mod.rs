@@ -1,4 +1,7 @@
+pub mod admission;
 pub mod cmd;
+pub mod latency;
+pub mod metrics;
 pub mod run;
 pub mod server;
 
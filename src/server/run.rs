@@ -1,26 +1,34 @@
 use std::net::SocketAddr;
 use std::pin::pin;
 use std::str::FromStr;
+use std::time::Duration;
 
 use face::server::FaceServer;
 use futures::future;
 use local_ip_address::local_ip;
+use tokio::select;
 use tokio::sync::{mpsc, Mutex};
 use tokio_util::sync::CancellationToken;
 use tonic::transport::{server::Router, Channel, Server as TransportServer};
+use tracing::{debug, error, info, warn};
 
 use crate::deployment::database::{DeploymentDatabase, Target};
-use crate::proto::{scheduler_client::SchedulerClient, JoinRequest};
+use crate::election::Election;
+use crate::node::NodeInfo;
+use crate::proto::{scheduler_client::SchedulerClient, JoinRequest, LeaveRequest};
+use crate::proto::{scheduler_server::SchedulerServer, server_daemon_server::ServerDaemonServer};
 use crate::report::MetricsReporter;
-use crate::scheduler::{AuthoritativeScheduler, Cluster};
-use crate::{
-    proto::{scheduler_server::SchedulerServer, server_daemon_server::ServerDaemonServer},
-    scheduler::mean::MeanScheduler,
-};
+use crate::scheduler::scale_out::ScaleOutConfig;
+use crate::scheduler::{registry::PolicyInfo, AuthoritativeScheduler, Cluster, ScaleInConfig};
+use crate::telemetry::{self, traced, LogConfig};
 use crate::{Error, GroupInfo, Result, ServerInfo};
 
 use crate::server::{ServerCommand, StartCommand};
 
+use super::admission::{AdmissionConfig, AdmissionControl};
+use super::latency::LatencyRecorder;
+use super::metrics::Metrics;
+
 use super::ServerDaemon;
 
 #[cfg(feature = "face")]
@@ -28,12 +36,29 @@ use face::proto as face_proto;
 
 pub const DEFAULT_HOST: &'static str = "127.0.0.1:50051";
 
+/// Offset from the listen port to the default port of the metrics endpoint
+const METRICS_PORT_OFFSET: u16 = 1000;
+
+const LEAVE_RETRIES: usize = 5;
+const LEAVE_RETRY_INTERVAL: Duration = Duration::from_millis(500);
+
 pub struct ServerRunner {
     command: ServerCommand,
     // FIXME: socket is not known at instantiation of this struct.
     // Instead, it will be known when it is the "start" command.
     socket: SocketAddr,
     database: DeploymentDatabase,
+    election: Election,
+    latency: LatencyRecorder,
+    admission: AdmissionControl,
+    metrics: Metrics,
+    /// The policy used when this server starts a cluster
+    policy: PolicyInfo,
+    /// Applied whenever this server becomes the scheduler
+    scale_in: ScaleInConfig,
+    scale_out: ScaleOutConfig,
+    /// Cancelled on SIGINT or SIGTERM
+    shutdown: CancellationToken,
     rx: Mutex<StateReceiver>,
     tx: StateSender,
 }
@@ -62,11 +87,27 @@ impl ServerRunner {
         let rx = Mutex::new(rx);
         let socket = DEFAULT_HOST.parse().expect("failed to parse default host");
         let database = DeploymentDatabase::default(tx.clone());
+        let election = Election::new();
+        let latency = LatencyRecorder::new();
+        let admission = AdmissionControl::new(AdmissionConfig::default());
+        let metrics = Metrics::new();
+        let policy = PolicyInfo::default();
+        let scale_in = ScaleInConfig::default();
+        let scale_out = ScaleOutConfig::default();
+        let shutdown = CancellationToken::new();
 
         Self {
             command,
             socket,
             database,
+            election,
+            latency,
+            admission,
+            metrics,
+            policy,
+            scale_in,
+            scale_out,
+            shutdown,
             rx,
             tx,
         }
@@ -83,17 +124,40 @@ impl ServerRunner {
     }
 
     pub async fn run_start(&mut self, start_command: &StartCommand) -> Result<()> {
+        telemetry::init(&Self::create_log_config(start_command))?;
+
         self.socket = Self::get_socket(start_command)?;
         let info = self.create_info(start_command)?;
 
-        let mut state = self.determine_state(start_command, &info);
+        self.admission = AdmissionControl::new(Self::create_admission(start_command));
+
+        self.policy = Self::create_policy(start_command);
+        // Fail fast on an unknown scheduler
+        self.policy.create()?;
+
+        self.scale_in = Self::create_scale_in(start_command);
+        self.scale_out = Self::create_scale_out(start_command);
+
+        let mut state = self.determine_state(start_command, &info)?;
+
+        self.listen_signals();
+        self.serve_metrics(start_command)?;
 
         loop {
             let daemon = self.create_daemon(info.clone(), state.clone());
             let service_future = self.start_service(daemon, state.clone());
 
             let mut rx = self.rx.lock().await;
-            let rcvd_future = rx.recv();
+            let shutdown = self.shutdown.clone();
+            let rcvd_future = async move {
+                loop {
+                    let command = rx.recv().await;
+                    // Let the service drain instead of restarting it
+                    if command.is_none() || !shutdown.is_cancelled() {
+                        return command;
+                    }
+                }
+            };
 
             // Terminate serving_future by selecting another future
             let state_command = match future::select(pin!(service_future), pin!(rcvd_future)).await
@@ -104,17 +168,33 @@ impl ServerRunner {
                 }
             };
 
+            if self.shutdown.is_cancelled() {
+                info!("left the cluster. Exiting...");
+                return Ok(());
+            }
+
             state = match state_command {
                 StateCommand::Keep => state,
                 StateCommand::Update(new) => new,
-                StateCommand::BecomeScheduler(cluster) => {
-                    let mean_scheduler = MeanScheduler {};
-                    let scheduler = AuthoritativeScheduler::new(
+                StateCommand::BecomeScheduler(mut cluster) => {
+                    self.election.observe(cluster.group.number).await;
+
+                    // The cluster may have been started by a server that knows other schedulers
+                    if let Err(e) = cluster.policy.create() {
+                        warn!("{e}. Using the local policy instead.");
+                        cluster.policy = self.policy.clone();
+                    }
+
+                    let scheduler = AuthoritativeScheduler::from_cluster(
                         cluster,
-                        Box::new(mean_scheduler),
                         self.tx.clone(),
                         self.database.clone(),
-                    );
+                    )?;
+
+                    // Let the members know the new scheduler, once it starts serving
+                    let cloned = scheduler.clone();
+                    tokio::spawn(async move { cloned.announce().await });
+
                     DaemonState::Authoritative(scheduler)
                 }
             };
@@ -130,17 +210,16 @@ impl ServerRunner {
 
         let new_state = match state {
             DaemonState::Joining(bootstrap_addr) => {
-                println!("Joining to a cluster...");
+                info!("joining a cluster...");
                 self.join_cluster(server, &bootstrap_addr).await
             }
             DaemonState::Running(group) => {
-                println!("Running a new server...");
-                println!("group = {:?}", group);
+                info!(group = group.number, scheduler = %group.scheduler_info.id, "running as a member");
 
                 self.start_running(daemon, server, group).await
             }
             DaemonState::Authoritative(scheduler) => {
-                println!("Running an Authoritative server...");
+                info!("running as the scheduler");
                 self.start_authoritative(daemon, scheduler).await
             }
             DaemonState::Failed => {
@@ -152,22 +231,24 @@ impl ServerRunner {
     }
 
     async fn join_cluster(&self, server: ServerInfo, addr: &str) -> Result<DaemonState> {
-        println!("Joining a cluster over {}...", addr);
+        info!(%addr, "joining a cluster");
 
         let mut client = self.scheduler_client(addr).await?;
 
         let request = JoinRequest {
             server: Some(server.clone().into()),
         };
-        let resp = client.join(request).await?.into_inner();
+        let resp = client.join(telemetry::request(request)).await?.into_inner();
 
-        println!("JoinResponse: {:?}", resp);
+        debug!(response = ?resp, "joined");
 
-        let group = resp
+        let group: GroupInfo = resp
             .group
             .expect("Group in response cannot be empty")
             .try_into()?;
 
+        self.election.heard(group.number).await;
+
         Ok(DaemonState::Running(group))
     }
 
@@ -186,18 +267,47 @@ impl ServerRunner {
             .group
             .scheduler_info
             .clone();
+        {
+            let mut runtime = scheduler.runtime.lock().await;
+            // Let the scheduler count its scale-outs in the metrics of this daemon
+            runtime.metrics = self.metrics.clone();
+            runtime.scale_in = self.scale_in.clone();
+            runtime.scale_out.config = self.scale_out.clone();
+        }
+
         let reporter_token = self.start_reporter(server.clone(), scheduler_info);
+        let scale_in_token = scheduler.start_scale_in().await;
+        let autoscaler_token = scheduler.start_autoscaler().await;
+        let detector_token = scheduler.start_failure_detector().await;
+
+        let grpc_server = self.common_services(daemon).await?.add_service(
+            self.metrics
+                .wrap(traced(SchedulerServer::new(scheduler.clone()))),
+        );
+
+        let leave = async {
+            self.shutdown.cancelled().await;
 
-        let grpc_server = self
-            .common_services(daemon)
-            .await?
-            .add_service(SchedulerServer::new(scheduler.clone()));
+            reporter_token.cancel();
+            scale_in_token.cancel();
+            autoscaler_token.cancel();
+            detector_token.cancel();
+
+            self.hand_over_and_leave(&server, &scheduler)
+                .await
+                .err()
+                .map(|e| error!("failed to leave the cluster: {e}"));
+        };
 
-        println!("Listening on {}...", self.socket);
-        grpc_server.serve(self.socket).await?;
+        info!(socket = %self.socket, "listening");
+        // Stops accepting requests and drains in-flight ones after leaving
+        grpc_server.serve_with_shutdown(self.socket, leave).await?;
 
-        println!("cancel reporter (authoritative)");
+        debug!("cancel reporter (authoritative)");
         reporter_token.cancel();
+        scale_in_token.cancel();
+        autoscaler_token.cancel();
+        detector_token.cancel();
 
         Ok(DaemonState::Authoritative(scheduler.clone()))
     }
@@ -212,19 +322,141 @@ impl ServerRunner {
 
         let grpc_router = self.common_services(daemon).await?;
 
-        grpc_router.serve(self.socket).await?;
+        let leave = async {
+            self.shutdown.cancelled().await;
+
+            reporter_token.cancel();
+
+            self.leave_cluster(&server, &group.scheduler_info)
+                .await
+                .err()
+                .map(|e| error!("failed to leave the cluster: {e}"));
+        };
+
+        // Stops accepting requests and drains in-flight ones after leaving
+        grpc_router.serve_with_shutdown(self.socket, leave).await?;
 
-        println!("cancel reporter (running)");
+        debug!("cancel reporter (running)");
         reporter_token.cancel();
 
         Ok(DaemonState::Running(group.clone()))
     }
 
+    /// hand_over_and_leave makes another member the scheduler, and then leaves the cluster.
+    async fn hand_over_and_leave(
+        &self,
+        server: &ServerInfo,
+        scheduler: &AuthoritativeScheduler,
+    ) -> Result<()> {
+        let Some(successor) = scheduler.successor().await else {
+            info!("no other server to hand over to. Stopping the cluster.");
+            return Ok(());
+        };
+
+        info!(successor = %successor.id, "handing over before leaving");
+        scheduler.hand_over(&successor).await?;
+
+        self.leave_cluster(server, &successor).await
+    }
+
+    /// leave_cluster asks the scheduler to move the instances on this server elsewhere,
+    /// and to remove this server from the cluster.
+    async fn leave_cluster(&self, server: &ServerInfo, scheduler: &ServerInfo) -> Result<()> {
+        info!(scheduler = %scheduler.id, "leaving the cluster");
+
+        let request = LeaveRequest {
+            server: Some(server.clone().into()),
+        };
+
+        let mut result = Ok(());
+
+        // A newly nominated scheduler may not be listening yet
+        for _ in 0..LEAVE_RETRIES {
+            result = match self.scheduler_client(&scheduler.addr).await {
+                Ok(mut client) => client
+                    .leave(telemetry::request(request.clone()))
+                    .await
+                    .map(|_| ())
+                    .map_err(Error::from),
+                Err(e) => Err(e),
+            };
+
+            if result.is_ok() {
+                break;
+            }
+            tokio::time::sleep(LEAVE_RETRY_INTERVAL).await;
+        }
+
+        result
+    }
+
+    /// listen_signals cancels the shutdown token on SIGINT or SIGTERM.
+    fn listen_signals(&self) {
+        let token = self.shutdown.clone();
+
+        tokio::spawn(async move {
+            match wait_for_signal().await {
+                Ok(()) => {
+                    info!("received a signal. Shutting down...");
+                    token.cancel();
+                }
+                Err(e) => error!("failed to listen for signals: {e}"),
+            }
+        });
+    }
+
+    /// serve_metrics exposes the metrics of this daemon over HTTP until it shuts down.
+    /// Fails if the address given by `--metrics-listen` cannot be bound, while the daemon
+    /// keeps running without the endpoint if the default one cannot.
+    fn serve_metrics(&self, start_command: &StartCommand) -> Result<()> {
+        let (addr, listener) = match &start_command.metrics_listen {
+            Some(addr) => {
+                let addr = SocketAddr::from_str(addr)
+                    .map_err(|e| format!("failed to parse metrics address: {e}"))?;
+                (addr, Metrics::bind(addr)?)
+            }
+            None => {
+                let Some(port) = self.socket.port().checked_add(METRICS_PORT_OFFSET) else {
+                    warn!("no default port for metrics. Serving without metrics");
+                    return Ok(());
+                };
+                let addr = SocketAddr::new(self.socket.ip(), port);
+                match Metrics::bind(addr) {
+                    Ok(listener) => (addr, listener),
+                    Err(e) => {
+                        warn!("{e}. Serving without metrics");
+                        return Ok(());
+                    }
+                }
+            }
+        };
+
+        let metrics = self.metrics.clone();
+        let shutdown = self.shutdown.clone();
+
+        tokio::spawn(async move {
+            info!(%addr, "serving metrics");
+            if let Err(e) = metrics.serve(listener, shutdown).await {
+                warn!("{e}");
+            }
+        });
+
+        Ok(())
+    }
+
     fn start_reporter(&self, server: ServerInfo, scheduler: ServerInfo) -> CancellationToken {
         let token = CancellationToken::new();
         let cloned = token.clone();
 
-        let mut reporter = MetricsReporter::new(self.tx.clone(), server, scheduler);
+        let mut reporter = MetricsReporter::new(
+            self.tx.clone(),
+            self.election.clone(),
+            self.latency.clone(),
+            self.admission.clone(),
+            self.metrics.clone(),
+            server,
+            scheduler,
+        );
         tokio::spawn(async move { reporter.start(cloned).await });
 
         token
@@ -232,13 +464,13 @@ impl ServerRunner {
 
     async fn common_services(&self, daemon: ServerDaemon) -> Result<Router> {
         let router = TransportServer::builder()
-            .add_service(ServerDaemonServer::new(daemon))
+            .add_service(self.metrics.wrap(traced(ServerDaemonServer::new(daemon))))
             .add_service(hello::proto::greeter_server::GreeterServer::new(
                 hello::MyGreeter::default(),
             ));
 
         #[cfg(feature = "face")]
-        let router = if let Some(deployment) = self.database.lookup("face").await {
+        let router = if let Some(deployment) = self.database.lookup_instance("face").await {
             use face_proto::detector_server::DetectorServer;
             let onnx = self
                 .database
@@ -256,7 +488,9 @@ impl ServerRunner {
                 .await
                 .map_err(|e| Error::AppInstantiation(e.to_string()))?;
             let server = DetectorServer::new(inner_server);
-            router.add_service(server)
+            // Latencies include the time waiting for admission
+            let server = self.admission.wrap(deployment.id, server);
+            router.add_service(self.latency.wrap(deployment.id, server))
         } else {
             router
         };
@@ -265,7 +499,13 @@ impl ServerRunner {
     }
 
     fn create_daemon(&self, info: ServerInfo, state: DaemonState) -> ServerDaemon {
-        ServerDaemon::with_state(state, info, self.tx.clone(), self.database.clone())
+        ServerDaemon::with_state(
+            state,
+            info,
+            self.tx.clone(),
+            self.database.clone(),
+            self.election.clone(),
+        )
     }
 
     fn create_info(&self, start_command: &StartCommand) -> Result<ServerInfo> {
@@ -277,14 +517,64 @@ impl ServerRunner {
 
         let host = format!("{ip}:{}", self.socket.port());
 
-        match &start_command.id {
-            Some(id) => ServerInfo::with_id_str(&id, &host),
-            None => Ok(ServerInfo::new(&host)),
+        let info = match &start_command.id {
+            Some(id) => ServerInfo::with_id_str(&id, &host)?,
+            None => ServerInfo::new(&host),
+        };
+
+        let labels = start_command.labels.iter().cloned().collect();
+        Ok(info.with_node(NodeInfo::detect(labels)))
+    }
+
+    fn create_policy(start_command: &StartCommand) -> PolicyInfo {
+        PolicyInfo {
+            name: start_command.scheduler.clone(),
+            scale_out_threshold: start_command.scale_out_threshold,
+            scale_in_threshold: start_command.scale_in_threshold,
+            stats_retention: start_command.stats_retention,
+            stats_half_life: start_command.stats_half_life_ms.map(Duration::from_millis),
+        }
+    }
+
+    fn create_scale_in(start_command: &StartCommand) -> ScaleInConfig {
+        let default = ScaleInConfig::default();
+        ScaleInConfig {
+            interval: start_command
+                .scale_in_interval_ms
+                .map_or(default.interval, Duration::from_millis),
+            window: start_command
+                .scale_in_window_ms
+                .map_or(default.window, Duration::from_millis),
+            ..default
+        }
+    }
+
+    fn create_scale_out(start_command: &StartCommand) -> ScaleOutConfig {
+        ScaleOutConfig {
+            max_replicas: start_command.max_replicas,
+            ..Default::default()
+        }
+    }
+
+    fn create_admission(start_command: &StartCommand) -> AdmissionConfig {
+        let default = AdmissionConfig::default();
+        AdmissionConfig {
+            max_in_flight: start_command.max_in_flight.unwrap_or(default.max_in_flight),
+            max_queue: start_command.max_queue.unwrap_or(default.max_queue),
+            queue_timeout: start_command
+                .queue_timeout_ms
+                .map_or(default.queue_timeout, Duration::from_millis),
+        }
+    }
+
+    fn create_log_config(start_command: &StartCommand) -> LogConfig {
+        LogConfig {
+            level: start_command.log_level.clone(),
+            format: start_command.log_format,
         }
     }
 
     fn get_socket(start_command: &StartCommand) -> Result<SocketAddr> {
-        println!("{}", &start_command.listen_host);
         Ok(SocketAddr::from_str(&start_command.listen_host)
             .map_err(|e| format!("failed to parse listen address: {e}"))?)
     }
@@ -293,26 +583,46 @@ impl ServerRunner {
         &self,
         start_command: &StartCommand,
         server: &ServerInfo,
-    ) -> DaemonState {
+    ) -> Result<DaemonState> {
         let maybe_bootstrap_addr = start_command.bootstrap_addr.as_deref();
 
-        match maybe_bootstrap_addr {
+        let state = match maybe_bootstrap_addr {
             Some(bootstrap_addr) => DaemonState::Joining(bootstrap_addr.to_owned()),
             None => {
-                let mean_scheduler = Box::new(MeanScheduler {});
                 let tx = self.tx.clone();
                 let scheduler = AuthoritativeScheduler::from_server(
                     server,
-                    mean_scheduler,
+                    self.policy.clone(),
                     tx,
                     self.database.clone(),
-                );
+                )?;
                 DaemonState::Authoritative(scheduler)
             }
-        }
+        };
+
+        Ok(state)
     }
 
     async fn scheduler_client(&self, target_addr: &str) -> Result<SchedulerClient<Channel>> {
         Ok(SchedulerClient::connect(target_addr.to_owned()).await?)
     }
 }
+
+#[cfg(unix)]
+async fn wait_for_signal() -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate())?;
+
+    select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = terminate.recv() => {}
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> Result<()> {
+    Ok(tokio::signal::ctrl_c().await?)
+}
@@ -3,23 +3,30 @@ use std::sync::Arc;
 use tokio::sync::Mutex;
 use tonic::Status;
 use tonic::{Request, Response};
+use tracing::{debug, info};
+use uuid::Uuid;
 
 use crate::deployment::database::DeploymentDatabase;
+use crate::election::Election;
 use crate::proto::server_daemon_server::ServerDaemon as ServerDaemonTrait;
 use crate::proto::{
-    DestroyRequest, DestroyResponse, GetInfoRequest, GetInfoResponse, MonitorRequest,
-    MonitorResponse, NominateRequest, NominateResponse, PingResponse, ServerState, SpawnRequest,
-    SpawnResponse,
+    DestroyReason, DestroyRequest, DestroyResponse, GetInfoRequest, GetInfoResponse, Group,
+    MonitorRequest, MonitorResponse, NominateRequest, NominateResponse, NotifyRequest,
+    NotifyResponse, PingResponse, ServerState, SpawnRequest, SpawnResponse, VoteRequest,
+    VoteResponse,
 };
-use crate::{Error as LaqistaError, RpcResult, ServerInfo};
+use crate::scheduler::Cluster;
+use crate::telemetry;
+use crate::{Error as LaqistaError, GroupInfo, RpcResult, ServerInfo};
 
-use super::{DaemonState, StateSender};
+use super::{DaemonState, StateCommand, StateSender};
 
 #[derive(Clone, Debug)]
 pub struct ServerDaemon {
     pub runtime: Arc<Mutex<ServerDaemonRuntime>>,
     pub tx: StateSender,
     pub state: DaemonState,
+    pub election: Election,
 }
 
 #[derive(Clone, Debug)]
@@ -34,10 +41,34 @@ impl ServerDaemon {
         info: ServerInfo,
         tx: StateSender,
         database: DeploymentDatabase,
+        election: Election,
     ) -> Self {
         let runtime = Arc::new(Mutex::new(ServerDaemonRuntime { info, database }));
 
-        Self { runtime, tx, state }
+        Self {
+            runtime,
+            tx,
+            state,
+            election,
+        }
+    }
+
+    /// check_group rejects requests from a scheduler of an older group.
+    async fn check_group(&self, group: Option<Group>) -> RpcResult<()> {
+        let number = match group {
+            Some(group) => group.number,
+            // Requests without a group are not from a scheduler
+            None => return Ok(()),
+        };
+        telemetry::record("group", number);
+
+        if !self.election.observe(number).await {
+            return Err(Status::aborted(format!(
+                "request from a stale scheduler (group {number})"
+            )));
+        }
+
+        Ok(())
     }
 }
 
@@ -47,7 +78,7 @@ impl ServerDaemonTrait for ServerDaemon {
         &self,
         _request: Request<GetInfoRequest>,
     ) -> RpcResult<Response<GetInfoResponse>> {
-        println!("GetInfo called!");
+        debug!("GetInfo called");
 
         let server = Some(self.runtime.lock().await.info.clone().into());
         let state = &self.state;
@@ -74,7 +105,7 @@ impl ServerDaemonTrait for ServerDaemon {
     }
 
     async fn ping(&self, _request: Request<()>) -> RpcResult<Response<PingResponse>> {
-        println!("got ping!");
+        debug!("got ping");
 
         let resposne = PingResponse { success: true };
 
@@ -83,10 +114,102 @@ impl ServerDaemonTrait for ServerDaemon {
 
     async fn nominate(
         &self,
-        _request: Request<NominateRequest>,
+        request: Request<NominateRequest>,
     ) -> RpcResult<Response<NominateResponse>> {
-        println!("got nominate!");
-        Err(Status::unimplemented("not implemented"))
+        let cluster_state = request
+            .into_inner()
+            .nomination
+            .and_then(|n| n.cluster)
+            .ok_or(Status::aborted("`nomination.cluster` is required"))?;
+
+        let cluster: Cluster = cluster_state
+            .try_into()
+            .map_err(<LaqistaError as Into<Status>>::into)?;
+
+        telemetry::record("group", cluster.group.number);
+        info!("nominated as the scheduler");
+
+        let this_id = self.runtime.lock().await.info.id;
+        if cluster.group.scheduler_info.id != this_id {
+            return Err(Status::aborted("nominated scheduler is not this server"));
+        }
+
+        if let DaemonState::Authoritative(_) = &self.state {
+            return Err(Status::aborted("this server is already authoritative"));
+        }
+
+        if !self.election.heard(cluster.group.number).await {
+            return Err(Status::aborted("nomination from a stale scheduler"));
+        }
+
+        self.tx
+            .send(StateCommand::BecomeScheduler(cluster))
+            .await
+            .map_err(|e| Status::aborted(format!("failed to become scheduler: {e}")))?;
+
+        Ok(Response::new(NominateResponse { success: true }))
+    }
+
+    async fn notify(&self, request: Request<NotifyRequest>) -> RpcResult<Response<NotifyResponse>> {
+        let group: GroupInfo = request
+            .into_inner()
+            .cluster
+            .and_then(|c| c.group)
+            .ok_or(Status::aborted("`cluster.group` is required"))?
+            .try_into()
+            .map_err(<LaqistaError as Into<Status>>::into)?;
+
+        telemetry::record("group", group.number);
+        debug!(scheduler = %group.scheduler_info.id, "notified of the scheduler");
+
+        if !self.election.heard(group.number).await {
+            return Err(Status::aborted("notification from a stale scheduler"));
+        }
+
+        // Nothing to do if we already follow the scheduler
+        let current = match &self.state {
+            DaemonState::Running(g) => Some(g.clone()),
+            DaemonState::Authoritative(s) => Some(s.runtime.lock().await.cluster.group.clone()),
+            _ => None,
+        };
+        if current.is_some_and(|g| {
+            g.number == group.number && g.scheduler_info.id == group.scheduler_info.id
+        }) {
+            return Ok(Response::new(NotifyResponse { success: true }));
+        }
+
+        // Restart the daemon so that it reports to the new scheduler.
+        // A stale authoritative server steps down here, too.
+        self.tx
+            .send(StateCommand::Update(DaemonState::Running(group)))
+            .await
+            .map_err(|e| Status::aborted(format!("failed to update state: {e}")))?;
+
+        Ok(Response::new(NotifyResponse { success: true }))
+    }
+
+    async fn request_vote(
+        &self,
+        request: Request<VoteRequest>,
+    ) -> RpcResult<Response<VoteResponse>> {
+        let group: GroupInfo = request
+            .into_inner()
+            .group
+            .ok_or(Status::aborted("`group` is required"))?
+            .try_into()
+            .map_err(<LaqistaError as Into<Status>>::into)?;
+
+        telemetry::record("group", group.number);
+
+        let response = self.election.vote(&group).await;
+        info!(
+            candidate = %group.scheduler_info.id,
+            term = group.number,
+            granted = response.granted,
+            "voted"
+        );
+
+        Ok(Response::new(response))
     }
 
     async fn monitor(
@@ -96,10 +219,12 @@ impl ServerDaemonTrait for ServerDaemon {
         Ok(Response::new(MonitorResponse { windows: vec![] }))
     }
     async fn spawn(&self, request: Request<SpawnRequest>) -> RpcResult<Response<SpawnResponse>> {
-        let deployment = request
-            .into_inner()
-            .deployment
-            .ok_or(Status::aborted("`deployment` is required`"))?;
+        let SpawnRequest { deployment, group } = request.into_inner();
+
+        self.check_group(group).await?;
+
+        let deployment = deployment.ok_or(Status::aborted("`deployment` is required`"))?;
+        telemetry::record("deployment_id", &deployment.id);
 
         let info = deployment
             .try_into()
@@ -129,8 +254,41 @@ impl ServerDaemonTrait for ServerDaemon {
     }
     async fn destroy(
         &self,
-        _request: Request<DestroyRequest>,
+        request: Request<DestroyRequest>,
     ) -> RpcResult<Response<DestroyResponse>> {
+        let request = request.into_inner();
+        let reason = request.reason();
+
+        self.check_group(request.group.clone()).await?;
+
+        let id = Uuid::parse_str(&request.app_id).map_err(|e| Status::aborted(e.to_string()))?;
+        telemetry::record("deployment_id", id);
+
+        let mut database = self.runtime.lock().await.database.clone();
+
+        // Keep the binaries of an instance that is not here
+        if !database.has_instance(&id).await {
+            return Err(Status::not_found(format!("Instance not found: {id}")));
+        }
+
+        match reason {
+            DestroyReason::ScaleIn => {}
+            // Removed applications are not expected to be spawned again, so drop the binaries too.
+            DestroyReason::Removed => {
+                database.remove_app(&id).await.map_err(|e| {
+                    Status::aborted(format!("failed to remove application from database: {e}"))
+                })?;
+            }
+            DestroyReason::Unknown => {
+                return Err(Status::aborted("`reason` is required"));
+            }
+        }
+
+        database.remove_instance(&id).await.map_err(|e| {
+            Status::aborted(format!("failed to remove instance from database: {e}"))
+        })?;
+        // Same as `spawn`, the restart of the daemon is signaled by DeploymentDatabase.
+
         Ok(Response::new(DestroyResponse { success: true }))
     }
 }
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::task::{Context, Poll};
+
+use clap::ValueEnum;
+use tonic::body::BoxBody;
+use tonic::codegen::{http, BoxFuture, Service};
+use tonic::metadata::MetadataValue;
+use tonic::server::NamedService;
+use tonic::Request;
+use tracing::{field, Instrument, Span};
+use tracing_subscriber::EnvFilter;
+use uuid::Uuid;
+
+use crate::utils::take_ready;
+
+/// Header of the W3C Trace Context, carried in gRPC metadata
+const TRACEPARENT_KEY: &str = "traceparent";
+
+tokio::task_local! {
+    /// Trace of the RPC that the current task is handling
+    static TRACE_ID: Uuid;
+}
+
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    /// Filter directives, e.g., "info" or "info,laqista::scheduler=debug"
+    pub level: String,
+    pub format: LogFormat,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// init installs the global subscriber. `RUST_LOG` takes precedence over the configured level.
+pub fn init(config: &LogConfig) -> crate::Result<()> {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(&config.level))
+        .map_err(|e| format!("invalid log level {:?}: {e}", config.level))?;
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = match config.format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().try_init(),
+    };
+
+    result.map_err(|e| format!("failed to initialize logging: {e}"))?;
+    Ok(())
+}
+
+/// current_trace_id returns the trace of the RPC being handled, if any.
+pub fn current_trace_id() -> Option<Uuid> {
+    TRACE_ID.try_with(|id| *id).ok()
+}
+
+/// request creates a request that continues the current trace, or starts a new one.
+pub fn request<T>(message: T) -> Request<T> {
+    let mut request = Request::new(message);
+
+    let trace_id = current_trace_id().unwrap_or_else(Uuid::new_v4);
+    if let Ok(value) = MetadataValue::try_from(traceparent(&trace_id)) {
+        request.metadata_mut().insert(TRACEPARENT_KEY, value);
+    }
+
+    request
+}
+
+/// in_current_trace makes a future that is to be spawned continue the current span and trace.
+pub fn in_current_trace<F: Future>(future: F) -> impl Future<Output = F::Output> {
+    let trace_id = current_trace_id().unwrap_or_else(Uuid::new_v4);
+    TRACE_ID.scope(trace_id, future).instrument(Span::current())
+}
+
+/// record fills a field of the current RPC span, i.e., "deployment_id", "server_id" or "group".
+pub fn record(name: &str, value: impl Display) {
+    Span::current().record(name, field::display(value));
+}
+
+fn traceparent(trace_id: &Uuid) -> String {
+    // Each request is a span of its own
+    let (span_id, _) = Uuid::new_v4().as_u64_pair();
+    format!("00-{}-{span_id:016x}-01", trace_id.simple())
+}
+
+fn parse_traceparent(value: &str) -> Option<Uuid> {
+    let mut parts = value.split('-');
+    let (version, trace_id) = (parts.next()?, parts.next()?);
+
+    if version != "00" || trace_id.len() != 32 {
+        return None;
+    }
+
+    Uuid::try_parse(trace_id).ok().filter(|id| !id.is_nil())
+}
+
+/// Traced wraps a gRPC service, and handles each request in a span of the trace it belongs to.
+#[derive(Clone, Debug)]
+pub struct Traced<S> {
+    inner: S,
+}
+
+pub fn traced<S>(inner: S) -> Traced<S> {
+    Traced { inner }
+}
+
+impl<S, B> Service<http::Request<B>> for Traced<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let mut inner = take_ready(&mut self.inner);
+
+        let trace_id = request
+            .headers()
+            .get(TRACEPARENT_KEY)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_traceparent)
+            .unwrap_or_else(Uuid::new_v4);
+
+        let span = tracing::info_span!(
+            "rpc",
+            method = request.uri().path(),
+            %trace_id,
+            deployment_id = field::Empty,
+            server_id = field::Empty,
+            group = field::Empty,
+        );
+
+        Box::pin(
+            TRACE_ID
+                .scope(trace_id, inner.call(request))
+                .instrument(span),
+        )
+    }
+}
+
+impl<S: NamedService> NamedService for Traced<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_traceparent_round_trip() {
+        let trace_id = Uuid::new_v4();
+        let value = traceparent(&trace_id);
+
+        assert_eq!(value.len(), 55);
+        assert_eq!(parse_traceparent(&value), Some(trace_id));
+    }
+
+    #[tokio::test]
+    async fn test_request_continues_trace() {
+        let trace_id = Uuid::new_v4();
+
+        let request = TRACE_ID.scope(trace_id, async { request(()) }).await;
+        let value = request.metadata().get(TRACEPARENT_KEY).unwrap();
+
+        assert_eq!(parse_traceparent(value.to_str().unwrap()), Some(trace_id));
+        assert_eq!(parse_traceparent("01-abc-def-00"), None);
+    }
+}
@@ -8,7 +8,7 @@ use mac_address::{get_mac_address, MacAddress, MacAddressError};
 use prost_types::Timestamp;
 use uuid::Uuid;
 
-use crate::proto::{AppInstanceLocations, ClusterState, Group, Server};
+use crate::proto::{AppInstanceLocations, CatalogEntry, ClusterState, Group, Server};
 
 #[derive(Clone, Debug)]
 pub struct IdMap<T: Clone + Debug>(pub HashMap<Uuid, T>);
@@ -52,11 +52,56 @@ pub fn subtract_window(end: &Timestamp, start: &Timestamp) -> i64 {
     (end_i128 - start_i128) as i64
 }
 
-pub fn mul_as_percent(x: i64, percent: i64) -> i64 {
-    let x = x as f64;
-    let y = percent as f64 / 100.;
+/// take_ready takes a service that has been polled ready, leaving a clone for the next call.
+/// The clone itself may not be ready, so the request has to be handled by the taken one.
+pub fn take_ready<S: Clone>(service: &mut S) -> S {
+    let clone = service.clone();
+    std::mem::replace(service, clone)
+}
+
+/// random_below returns a random number in `0..n`. `n` must be positive.
+pub fn random_below(n: usize) -> usize {
+    Rng::from_entropy().below(n as u64) as usize
+}
+
+/// Rng is xorshift64*. It is not cryptographically secure, but the same seed reproduces
+/// the same sequence, e.g., of a synthetic trace.
+#[derive(Clone, Debug)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Zero is a fixed point of xorshift
+        Self(seed.max(1))
+    }
+
+    pub fn from_entropy() -> Self {
+        // UUIDv4 is random enough for a seed, and saves another dependency
+        Self::new(Uuid::new_v4().as_u64_pair().0)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// below returns a number in `0..n`. `n` must be positive.
+    pub fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// unit returns a number in `0..1`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
 
-    (x * y) as i64
+    /// uuid returns a random UUIDv4.
+    pub fn uuid(&mut self) -> Uuid {
+        let bytes = ((self.next() as u128) << 64 | self.next() as u128).to_le_bytes();
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    }
 }
 
 pub fn datetime_to_prost(dt: DateTime<Utc>) -> Timestamp {
@@ -77,7 +122,15 @@ pub fn cluster_differs(a: &ClusterState, b: &ClusterState) -> bool {
 
     let instances_changed = instances_differ(&a.instances, &b.instances);
 
-    return group_changed || servers_changed || instances_changed;
+    let catalog_changed = catalog_differs(&a.catalog, &b.catalog);
+
+    let policy_changed = a.policy != b.policy;
+
+    return group_changed
+        || servers_changed
+        || instances_changed
+        || catalog_changed
+        || policy_changed;
 }
 
 pub fn group_differs(a: &Group, b: &Group) -> bool {
@@ -100,6 +153,24 @@ pub fn instances_differ(a: &Vec<AppInstanceLocations>, b: &Vec<AppInstanceLocati
     a.len() != b.len()
 }
 
+pub fn catalog_differs(a: &Vec<CatalogEntry>, b: &Vec<CatalogEntry>) -> bool {
+    let ids = |entries: &Vec<CatalogEntry>| {
+        let mut ids: Vec<_> = entries
+            .iter()
+            .map(|e| {
+                (
+                    e.deployment.as_ref().map(|d| d.id.clone()),
+                    e.versions.len(),
+                )
+            })
+            .collect();
+        ids.sort();
+        ids
+    };
+
+    ids(a) != ids(b)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -0,0 +1,131 @@
+use std::time::Duration;
+
+use laqista::deployment::database::DeploymentDatabase;
+use laqista::election::Election;
+use laqista::proto::server_daemon_client::ServerDaemonClient;
+use laqista::proto::server_daemon_server::ServerDaemonServer;
+use laqista::proto::SpawnRequest;
+use laqista::scheduler::Cluster;
+use laqista::server::{DaemonState, ServerDaemon};
+use laqista::{GroupInfo, ServerInfo};
+use laqista_core::DeploymentInfo;
+use tokio::sync::mpsc;
+use tonic::transport::Server;
+use tonic::Code;
+use uuid::Uuid;
+
+/// Starts a daemon on `host` in background, and returns its info and election state.
+async fn start_daemon(host: &str, group: &GroupInfo) -> (ServerInfo, Election) {
+    let info = ServerInfo::with_id(host, Uuid::new_v4());
+    let election = Election::new();
+
+    let (tx, mut rx) = mpsc::channel(1);
+    let root = tempfile::tempdir().expect("failed to create a temporary directory");
+    let database = DeploymentDatabase::read_dir(root.path().to_owned(), tx.clone())
+        .expect("failed to open database");
+
+    let daemon = ServerDaemon::with_state(
+        DaemonState::Running(group.clone()),
+        info.clone(),
+        tx,
+        database,
+        election.clone(),
+    );
+
+    let addr = host.parse().unwrap();
+    tokio::spawn(async move {
+        // Keep the database until the server stops
+        let _root = root;
+        Server::builder()
+            .add_service(ServerDaemonServer::new(daemon))
+            .serve(addr)
+            .await
+            .unwrap()
+    });
+    // Drop state commands, as there is no runner
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    (info, election)
+}
+
+/// Creates a cluster whose scheduler has failed and is not listening.
+fn failed_cluster() -> Cluster {
+    let scheduler = ServerInfo::with_id("127.0.0.1:50160", Uuid::new_v4());
+    Cluster::new(&scheduler)
+}
+
+#[tokio::test]
+async fn candidate_wins_with_majority() {
+    let mut cluster = failed_cluster();
+    let group = cluster.group.clone();
+
+    let (b, _) = start_daemon("127.0.0.1:50161", &group).await;
+    let (c, _) = start_daemon("127.0.0.1:50162", &group).await;
+    let candidate = ServerInfo::with_id("127.0.0.1:50163", Uuid::new_v4());
+
+    cluster.servers.extend([b, c, candidate.clone()]);
+
+    let won = Election::new().run(&cluster, &candidate).await.unwrap();
+    assert_eq!(won, Some(1));
+}
+
+#[tokio::test]
+async fn votes_once_per_term() {
+    let election = Election::new();
+
+    let a = ServerInfo::with_id("127.0.0.1:50171", Uuid::new_v4());
+    let b = ServerInfo::with_id("127.0.0.1:50172", Uuid::new_v4());
+
+    assert!(election.vote(&GroupInfo::with_number(&a, 1)).await.granted);
+    assert!(!election.vote(&GroupInfo::with_number(&b, 1)).await.granted);
+
+    // A newer term can be voted again
+    assert!(election.vote(&GroupInfo::with_number(&b, 2)).await.granted);
+
+    // An older term cannot
+    assert!(!election.vote(&GroupInfo::with_number(&a, 1)).await.granted);
+}
+
+#[tokio::test]
+async fn candidate_without_majority_loses() {
+    let mut cluster = failed_cluster();
+    let group = cluster.group.clone();
+
+    let (b, _) = start_daemon("127.0.0.1:50181", &group).await;
+    // Not listening
+    let c = ServerInfo::with_id("127.0.0.1:50182", Uuid::new_v4());
+    let d = ServerInfo::with_id("127.0.0.1:50183", Uuid::new_v4());
+    let candidate = ServerInfo::with_id("127.0.0.1:50184", Uuid::new_v4());
+
+    cluster.servers.extend([b, c, d, candidate.clone()]);
+
+    let won = Election::new().run(&cluster, &candidate).await.unwrap();
+    assert_eq!(won, None);
+}
+
+#[tokio::test]
+async fn stale_scheduler_is_fenced() {
+    let cluster = failed_cluster();
+    let group = cluster.group.clone();
+
+    let (b, election_b) = start_daemon("127.0.0.1:50191", &group).await;
+    let addr = "http://127.0.0.1:50191".to_owned();
+
+    // `b` has seen the term 2
+    assert!(election_b.observe(2).await);
+
+    let stale_group = GroupInfo::with_number(&b, 1);
+    let deployment = DeploymentInfo::new("test".to_owned(), "https://example.com".to_owned());
+    let request = SpawnRequest {
+        deployment: Some(deployment.into()),
+        group: Some(stale_group.into()),
+    };
+
+    let mut client = ServerDaemonClient::connect(addr).await.unwrap();
+    let status = client.spawn(request).await.unwrap_err();
+
+    assert_eq!(status.code(), Code::Aborted);
+    assert_eq!(status.message(), "request from a stale scheduler (group 1)");
+}
@@ -1,6 +1,7 @@
-use face::proto::InferRequest;
+use face::proto::detector_client::DetectorClient;
+use face::proto::{DetectionRequest, InferRequest};
 use image::{imageops::FilterType, GenericImageView, Pixel};
-use laqista::proto::{self, DeployRequest, LookupRequest};
+use laqista::proto::{self, DeployRequest, ExecutionMode, LookupRequest};
 
 static JPEG: &'static [u8] = include_bytes!("../data/sized-pelican.jpeg");
 static LABELS: &'static str = include_str!("../data/models/resnet-labels.txt");
@@ -16,15 +17,12 @@ async fn schedule_wasm() {
         .await
         .expect("failed to connect to the server");
 
-    let mut detector_client =
-        face::proto::detector_client::DetectorClient::connect(addr.to_owned())
-            .await
-            .unwrap();
-
     let request = DeployRequest {
         name: "face".to_owned(),
         source: "https://github.com/kino-ma/Laqista/releases/download/v0.1.0/face_v0.1.0.tgz"
             .to_owned(),
+        resources: None,
+        constraints: None,
     };
 
     let deployment = client
@@ -38,8 +36,59 @@ async fn schedule_wasm() {
         qos: None,
     };
 
-    let _resp = client.clone().lookup(request).await.unwrap().into_inner();
+    let resp = client.clone().lookup(request).await.unwrap().into_inner();
+    let mode = resp.mode();
+
+    let mut detector_client = DetectorClient::connect(resp.server.unwrap().addr)
+        .await
+        .unwrap();
+
+    // n02051845 pelican
+    let expected_idx = 144usize;
+    let labels: Vec<_> = LABELS.lines().collect();
+
+    match mode {
+        // WASM on the CPU takes the image as is, and returns the most probable label
+        ExecutionMode::Cpu => {
+            let request = DetectionRequest {
+                image_png: JPEG.to_vec(),
+            };
+            let resp = detector_client
+                .run_detection(request)
+                .await
+                .unwrap()
+                .into_inner();
+
+            assert!(
+                labels.contains(&resp.label.as_str()),
+                "label = {}",
+                resp.label
+            );
+        }
+        ExecutionMode::Gpu | ExecutionMode::Unspecified => {
+            let request = InferRequest { data: tensor() };
+
+            let resp = detector_client.infer(request).await.unwrap().into_inner();
+            let mut probs: Vec<_> = resp
+                .squeezenet0_flatten0_reshape0
+                .iter()
+                .enumerate()
+                .collect();
+
+            probs.sort_unstable_by(|a, b| b.1.partial_cmp(a.1).unwrap());
+
+            let top5 = &probs[..5];
+            let is_top5 = top5.iter().any(|(i, _)| *i == expected_idx);
+
+            let top5_labels: Vec<_> = top5.iter().map(|i| (i, &labels[i.0])).collect();
+
+            assert!(is_top5, "top5 = {:?}", top5_labels);
+        }
+    }
+}
 
+/// tensor resizes the image into the input of the model, in the range of [0, 1].
+fn tensor() -> Vec<f32> {
     let img = image::load_from_memory(&JPEG).expect("Failed to load image");
 
     let img = img.resize_to_fill(IMAGE_WIDTH as _, IMAGE_HEIGHT as _, FilterType::Nearest);
@@ -52,32 +101,8 @@ async fn schedule_wasm() {
         (channels[c] as f32) / 255.0
     });
 
-    let input = array.as_slice().expect("Failed to get array slice");
-
-    // let mut app_client = app::proto::greeter_client::GreeterClient::connect(addr)
-    //     .await
-    //     .unwrap();
-    let request = InferRequest {
-        data: input.to_vec(),
-    };
-
-    let resp = detector_client.infer(request).await.unwrap().into_inner();
-    let mut probs: Vec<_> = resp
-        .squeezenet0_flatten0_reshape0
-        .iter()
-        .enumerate()
-        .collect();
-
-    probs.sort_unstable_by(|a, b| b.1.partial_cmp(a.1).unwrap());
-
-    // n02051845 pelican
-    let expected_idx = 144usize;
-
-    let top5 = &probs[..5];
-    let is_top5 = top5.iter().any(|(i, _)| *i == expected_idx);
-
-    let labels: Vec<_> = LABELS.lines().collect();
-    let top5_labels: Vec<_> = top5.iter().map(|i| (i, &labels[i.0])).collect();
-
-    assert!(is_top5, "top5 = {:?}", top5_labels);
+    array
+        .as_slice()
+        .expect("Failed to get array slice")
+        .to_vec()
 }
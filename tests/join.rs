@@ -0,0 +1,149 @@
+use std::time::Duration;
+
+use laqista::deployment::database::DeploymentDatabase;
+use laqista::election::Election;
+use laqista::proto::scheduler_client::SchedulerClient;
+use laqista::proto::scheduler_server::SchedulerServer;
+use laqista::proto::server_daemon_server::ServerDaemonServer;
+use laqista::proto::{JoinRequest, Server as ProtoServer};
+use laqista::scheduler::{mean::MeanScheduler, AuthoritativeScheduler, Cluster};
+use laqista::server::{DaemonState, ServerDaemon};
+use laqista::ServerInfo;
+use tokio::sync::mpsc;
+use tonic::transport::Server;
+use tonic::Code;
+use uuid::Uuid;
+
+/// Starts a scheduler on `host` in background, and returns it.
+async fn start_scheduler(host: &str) -> AuthoritativeScheduler {
+    let info = ServerInfo::with_id(host, Uuid::new_v4());
+
+    let (tx, mut rx) = mpsc::channel(1);
+    let root = tempfile::tempdir().unwrap();
+    let database = DeploymentDatabase::read_dir(root.path().to_owned(), tx.clone())
+        .expect("failed to open database");
+
+    let scheduler = AuthoritativeScheduler::new(
+        Cluster::new(&info),
+        Box::new(MeanScheduler::default()),
+        tx,
+        database,
+    );
+
+    let service = SchedulerServer::new(scheduler.clone());
+    let addr = host.parse().unwrap();
+    tokio::spawn(async move {
+        // Keep the database until the server stops
+        let _root = root;
+        Server::builder()
+            .add_service(service)
+            .serve(addr)
+            .await
+            .unwrap()
+    });
+    // Drop state commands, as there is no runner
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    scheduler
+}
+
+/// Starts a daemon with `info` in background.
+async fn start_daemon(info: &ServerInfo, host: &str, scheduler: &AuthoritativeScheduler) {
+    let group = scheduler.runtime.lock().await.cluster.group.clone();
+
+    let (tx, mut rx) = mpsc::channel(1);
+    let root = tempfile::tempdir().unwrap();
+    let database = DeploymentDatabase::read_dir(root.path().to_owned(), tx.clone())
+        .expect("failed to open database");
+
+    let daemon = ServerDaemon::with_state(
+        DaemonState::Running(group),
+        info.clone(),
+        tx,
+        database,
+        Election::new(),
+    );
+
+    let addr = host.parse().unwrap();
+    tokio::spawn(async move {
+        let _root = root;
+        Server::builder()
+            .add_service(ServerDaemonServer::new(daemon))
+            .serve(addr)
+            .await
+            .unwrap()
+    });
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+}
+
+async fn join(scheduler_host: &str, server: &ServerInfo) -> Result<(), tonic::Status> {
+    let mut client = SchedulerClient::connect(format!("http://{scheduler_host}"))
+        .await
+        .unwrap();
+
+    let request = JoinRequest {
+        server: Some(server.clone().into()),
+    };
+    let response = client.join(request).await?.into_inner();
+
+    assert!(response.success);
+    assert!(response.cluster.is_some());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn join_is_idempotent() {
+    let scheduler = start_scheduler("127.0.0.1:50201").await;
+    let server = ServerInfo::with_id("127.0.0.1:50202", Uuid::new_v4());
+
+    join("127.0.0.1:50201", &server).await.unwrap();
+    join("127.0.0.1:50201", &server).await.unwrap();
+
+    assert_eq!(scheduler.runtime.lock().await.cluster.servers.len(), 2);
+}
+
+#[tokio::test]
+async fn rejoin_updates_address() {
+    let scheduler = start_scheduler("127.0.0.1:50211").await;
+    let id = Uuid::new_v4();
+
+    // Not listening, e.g., the server has restarted on another address
+    let before = ServerInfo::with_id("127.0.0.1:50212", id);
+    let after = ServerInfo::with_id("127.0.0.1:50213", id);
+
+    join("127.0.0.1:50211", &before).await.unwrap();
+    join("127.0.0.1:50211", &after).await.unwrap();
+
+    let servers: Vec<ProtoServer> = scheduler
+        .runtime
+        .lock()
+        .await
+        .cluster
+        .servers
+        .iter()
+        .map(|s| s.clone().into())
+        .collect();
+    assert_eq!(servers.len(), 2);
+    assert!(servers.iter().any(|s| s.addr == "http://127.0.0.1:50213"));
+}
+
+#[tokio::test]
+async fn conflicting_id_is_rejected() {
+    let scheduler = start_scheduler("127.0.0.1:50221").await;
+
+    let id = Uuid::new_v4();
+    let running = ServerInfo::with_id("127.0.0.1:50222", id);
+    start_daemon(&running, "127.0.0.1:50222", &scheduler).await;
+    join("127.0.0.1:50221", &running).await.unwrap();
+
+    let impostor = ServerInfo::with_id("127.0.0.1:50223", id);
+    let status = join("127.0.0.1:50221", &impostor).await.unwrap_err();
+
+    assert_eq!(status.code(), Code::AlreadyExists);
+    assert_eq!(scheduler.runtime.lock().await.cluster.servers.len(), 2);
+}
@@ -16,6 +16,8 @@ async fn schedule_wasm() {
         name: "face".to_owned(),
         source: "https://github.com/kino-ma/Laqista/releases/download/v0.1.0/face_v0.1.0.tgz"
             .to_owned(),
+        resources: None,
+        constraints: None,
     };
 
     let deployment = client